@@ -1,5 +1,20 @@
+use std::fs;
+use std::io::{ Seek, SeekFrom };
 use t1gars::*;
 
+// Writes an uncompressed tga file without image ID or color map.
+// Rows are given top to bottom.
+fn write_raw_tga(path: &str, image_type: u8, width: u16, height: u16, pixel_depth: u8, pixels: &[u8]) {
+    let mut bytes = vec![0u8; 18];
+    bytes[2] = image_type;
+    bytes[12..14].copy_from_slice(&width.to_le_bytes());
+    bytes[14..16].copy_from_slice(&height.to_le_bytes());
+    bytes[16] = pixel_depth;
+    bytes[17] = 0x20;
+    bytes.extend_from_slice(pixels);
+    fs::write(path, bytes).unwrap();
+}
+
 fn test_cbw8() -> Result<(), Error> {
     let tga = Tga::new("example/images/CBW8.TGA")?;
     assert_eq!(tga.header.get_pixel_format().unwrap(), TgaPixelFormat::BW8);
@@ -24,8 +39,2029 @@ fn test_utc24() -> Result<(), Error> {
     Ok(())
 }
 
+// Writes a 2x1 true color image carrying an unused color map of 3 entries starting at 5.
+fn write_unused_map_tga(path: &str) {
+    let mut bytes = vec![0u8; 18];
+    bytes[1] = 1;
+    bytes[2] = 2;
+    bytes[3..5].copy_from_slice(&5u16.to_le_bytes());
+    bytes[5..7].copy_from_slice(&3u16.to_le_bytes());
+    bytes[7] = 24;
+    bytes[12] = 2;
+    bytes[14] = 1;
+    bytes[16] = 24;
+    bytes[17] = 0x20;
+    bytes.extend_from_slice(&[0xee; 9]);
+    bytes.extend_from_slice(&[1, 2, 3, 4, 5, 6]);
+    fs::write(path, bytes).unwrap();
+}
+
+// Saves the image and returns the pixel data that was written.
+fn saved_pixels(tga: &Tga, path: &str) -> Result<Vec<u8>, Error> {
+    tga.save(path)?;
+    let bytes = fs::read(path)?;
+    Ok(bytes[18 + bytes[0] as usize..bytes.len() - 26].to_vec())
+}
+
+fn test_sobel_edges() -> Result<(), Error> {
+    // 8x4 grayscale image, black on the left half and white on the right half.
+    let pixels: Vec<u8> = (0..32).map(|i| if i % 8 < 4 { 0 } else { 255 }).collect();
+    write_raw_tga("example/images/temp_edge.tga", 3, 8, 4, 8, &pixels);
+    let edges = Tga::new("example/images/temp_edge.tga")?.sobel_edges()?;
+    assert_eq!(edges.header.get_pixel_format().unwrap(), TgaPixelFormat::BW8);
+    for row in saved_pixels(&edges, "example/images/temp_edges.tga")?.chunks(8) {
+        assert_eq!(row, [0, 0, 0, 255, 255, 0, 0, 0]);
+    }
+
+    Ok(())
+}
+
+fn test_box_blur() -> Result<(), Error> {
+    // 5x5 grayscale image with a single white pixel in the center.
+    let mut pixels = [0u8; 25];
+    pixels[12] = 255;
+    write_raw_tga("example/images/temp_dot.tga", 3, 5, 5, 8, &pixels);
+    let mut tga = Tga::new("example/images/temp_dot.tga")?;
+    tga.box_blur(1)?;
+    let blurred = saved_pixels(&tga, "example/images/temp_blur.tga")?;
+    for y in 0..5 {
+        for x in 0..5 {
+            let expected = if (1..4).contains(&x) && (1..4).contains(&y) { 28 } else { 0 };
+            assert_eq!(blurred[y * 5 + x], expected);
+        }
+    }
+
+    Ok(())
+}
+
+fn test_heightmap_to_normal() -> Result<(), Error> {
+    write_raw_tga("example/images/temp_flat.tga", 3, 4, 4, 8, &[100; 16]);
+    let normals = Tga::new("example/images/temp_flat.tga")?.heightmap_to_normal(2.0)?;
+    assert_eq!(normals.header.get_pixel_format().unwrap(), TgaPixelFormat::RGB24);
+    for px in saved_pixels(&normals, "example/images/temp_normals.tga")?.chunks(3) {
+        // BGR order of the neutral normal (128, 128, 255).
+        assert_eq!(px, [255, 128, 128]);
+    }
+
+    Ok(())
+}
+
+fn test_split_merge_channels() -> Result<(), Error> {
+    let tga = Tga::new("example/images/UTC24.TGA")?;
+    let channels = tga.split_channels()?;
+    assert_eq!(channels.len(), 3);
+    let merged = Tga::merge_channels(&[&channels[0], &channels[1], &channels[2]])?;
+    assert_eq!(saved_pixels(&tga, "example/images/temp_split.tga")?,
+        saved_pixels(&merged, "example/images/temp_merged.tga")?);
+
+    Ok(())
+}
+
+fn test_pack_channels() -> Result<(), Error> {
+    let mut sources = Vec::new();
+    for (i, value) in [10u8, 20, 30, 40].iter().enumerate() {
+        let path = format!("example/images/temp_gray{}.tga", i);
+        write_raw_tga(&path, 3, 2, 2, 8, &[*value; 4]);
+        sources.push(Tga::new(&path)?);
+    }
+    let packed = Tga::pack_channels(&sources[0], &sources[1], &sources[2], Some(&sources[3]))?;
+    assert_eq!(packed.header.get_pixel_format().unwrap(), TgaPixelFormat::ARGB32);
+    for px in saved_pixels(&packed, "example/images/temp_packed.tga")?.chunks(4) {
+        assert_eq!(px, [30, 20, 10, 40]);
+    }
+    let packed = Tga::pack_channels(&sources[0], &sources[1], &sources[2], None)?;
+    assert_eq!(packed.header.get_pixel_format().unwrap(), TgaPixelFormat::RGB24);
+
+    Ok(())
+}
+
+fn test_rle_modes() -> Result<(), Error> {
+    Tga::new("example/images/UTC24.TGA")?.save("example/images/temp_plain.tga")?;
+    let expected = saved_pixels(&Tga::new("example/images/temp_plain.tga")?, "example/images/temp_plain_out.tga")?;
+    for mode in [RleMode::PerScanline, RleMode::Continuous] {
+        Tga::new("example/images/UTC24.TGA")?.save_rle("example/images/temp_rle.tga", mode)?;
+        let options = DecodeOptions { rle_mode: mode, ..DecodeOptions::default() };
+        let tga = Tga::new_with_options("example/images/temp_rle.tga", &options)?;
+        assert_eq!(saved_pixels(&tga, "example/images/temp_rle_out.tga")?, expected);
+    }
+
+    // A solid image encoded continuously has runs crossing scanlines, which only the
+    // continuous decoding accepts. The default is per scanline.
+    write_raw_tga("example/images/temp_solid.tga", 3, 4, 4, 8, &[7; 16]);
+    Tga::new("example/images/temp_solid.tga")?.save_rle("example/images/temp_solid_rle.tga", RleMode::Continuous)?;
+    assert_eq!(fs::read("example/images/temp_solid_rle.tga")?.len(), 18 + 2 + 26);
+    let strict = SaveOptions { footer: false };
+    Tga::new("example/images/temp_solid.tga")?.save_rle_with_options("example/images/temp_solid_rle.tga", RleMode::Continuous, &strict)?;
+    assert_eq!(fs::read("example/images/temp_solid_rle.tga")?.len(), 18 + 2);
+    let continuous = DecodeOptions { rle_mode: RleMode::Continuous, ..DecodeOptions::default() };
+    assert!(Tga::new_with_options("example/images/temp_solid_rle.tga", &continuous).is_ok());
+    assert!(matches!(Tga::new("example/images/temp_solid_rle.tga"), Err(Error::IllegalHeader)));
+
+    Ok(())
+}
+
+fn test_save_rle_per_scanline() -> Result<(), Error> {
+    for name in ["UBW8", "UTC16", "UTC24", "UTC32"] {
+        let tga = Tga::new(&format!("example/images/{}.TGA", name))?;
+        tga.save_rle("example/images/temp_rle_scanline.tga", RleMode::default())?;
+        let reloaded = Tga::new("example/images/temp_rle_scanline.tga")?;
+        assert_eq!(reloaded.info, tga.info);
+        assert_eq!(reloaded, tga);
+    }
+
+    // 200 identical pixels per row: a 128 pixels run then a 72 pixels run, per row.
+    let tga = Tga::new_gray8(200, 3, vec![9; 600])?;
+    tga.save_rle("example/images/temp_rle_scanline.tga", RleMode::PerScanline)?;
+    let bytes = fs::read("example/images/temp_rle_scanline.tga")?;
+    assert_eq!(bytes[2], 11);
+    assert_eq!(bytes[18..bytes.len() - 26], [0xff, 9, 0xc7, 9].repeat(3));
+    assert_eq!(&bytes[bytes.len() - 18..], b"TRUEVISION-XFILE.\0");
+
+    Ok(())
+}
+
+fn test_require_format() -> Result<(), Error> {
+    let tga = Tga::new("example/images/UTC24.TGA")?;
+    tga.require_format(&[TgaPixelFormat::RGB24])?;
+    tga.require_format(&[TgaPixelFormat::BW8, TgaPixelFormat::RGB24])?;
+    assert!(matches!(tga.require_format(&[TgaPixelFormat::BW8]), Err(Error::UnsupportedPixelFormat)));
+    assert!(matches!(tga.require_format(&[]), Err(Error::UnsupportedPixelFormat)));
+
+    Ok(())
+}
+
+fn test_as_rgba_chunks() -> Result<(), Error> {
+    let tga = Tga::new("example/images/UTC32.TGA")?;
+    let chunks = tga.as_rgba_chunks()?;
+    assert_eq!(chunks.len(), tga.info.width as usize * tga.info.height as usize);
+    assert!(Tga::new("example/images/UTC24.TGA")?.as_rgba_chunks().is_err());
+
+    Ok(())
+}
+
+fn test_dhash() -> Result<(), Error> {
+    let pattern = |x: usize, y: usize, bright: usize| (((x * 37 + y * 91) ^ (x * y)) % 200 + bright) as u8;
+    let original: Vec<u8> = (0..36 * 32).map(|i| pattern(i % 36, i / 36, 0)).collect();
+    let brighter: Vec<u8> = (0..36 * 32).map(|i| pattern(i % 36, i / 36, 20)).collect();
+    let mirrored: Vec<u8> = (0..36 * 32).map(|i| pattern(35 - i % 36, i / 36, 0)).collect();
+    write_raw_tga("example/images/temp_hash_a.tga", 3, 36, 32, 8, &original);
+    write_raw_tga("example/images/temp_hash_b.tga", 3, 36, 32, 8, &brighter);
+    write_raw_tga("example/images/temp_hash_c.tga", 3, 36, 32, 8, &mirrored);
+    let a = Tga::new("example/images/temp_hash_a.tga")?.dhash();
+    let b = Tga::new("example/images/temp_hash_b.tga")?.dhash();
+    let c = Tga::new("example/images/temp_hash_c.tga")?.dhash();
+    assert!(hamming_distance(a, b) <= 4);
+    assert!(hamming_distance(a, c) >= 20);
+
+    Ok(())
+}
+
+fn test_skip_unused_color_map() -> Result<(), Error> {
+    write_unused_map_tga("example/images/temp_unused_map.tga");
+    let tga = Tga::new("example/images/temp_unused_map.tga")?;
+    assert_eq!(saved_pixels(&tga, "example/images/temp_unused_map_out.tga")?, [1, 2, 3, 4, 5, 6]);
+
+    Ok(())
+}
+
+fn test_normalize_for_atlas() -> Result<(), Error> {
+    // 6x5 grayscale image with a 2x2 block of content at (2, 1).
+    let mut pixels = [0u8; 30];
+    for i in [8, 9, 14, 15] {
+        pixels[i] = 200;
+    }
+    write_raw_tga("example/images/temp_sprite.tga", 3, 6, 5, 8, &pixels);
+    let (tga, rect) = Tga::new("example/images/temp_sprite.tga")?.normalize_for_atlas(1, &[0])?;
+    assert_eq!(rect, Rect { x: 2, y: 1, width: 2, height: 2 });
+    assert_eq!((tga.info.width, tga.info.height), (4, 4));
+    assert_eq!(saved_pixels(&tga, "example/images/temp_sprite_out.tga")?,
+        [0, 0, 0, 0, 0, 200, 200, 0, 0, 200, 200, 0, 0, 0, 0, 0]);
+
+    Ok(())
+}
+
+fn test_from_rows() -> Result<(), Error> {
+    let info = TgaInfo::for_format(2, 3, TgaPixelFormat::BW8);
+    let rows = (0..3u8).map(|y| vec![y * 10, y * 10 + 1]);
+    let tga = Tga::from_rows(info, rows)?;
+    assert_eq!(saved_pixels(&tga, "example/images/temp_rows.tga")?, [0, 1, 10, 11, 20, 21]);
+
+    let info = TgaInfo::for_format(2, 3, TgaPixelFormat::BW8);
+    assert!(Tga::from_rows(info, vec![vec![0, 1], vec![2, 3]]).is_err());
+    let info = TgaInfo::for_format(2, 1, TgaPixelFormat::BW8);
+    assert!(Tga::from_rows(info, vec![vec![0, 1, 2]]).is_err());
+
+    Ok(())
+}
+
+fn test_image_id() -> Result<(), Error> {
+    let mut tga = Tga::new("example/images/UTC24.TGA")?;
+    assert_eq!(tga.image_id.len(), 26);
+    for len in [0, 255] {
+        let id = vec![b'x'; len];
+        tga.set_image_id(&id)?;
+        tga.save("example/images/temp_id.tga")?;
+        assert_eq!(fs::read("example/images/temp_id.tga")?[0] as usize, len);
+        let reloaded = Tga::new("example/images/temp_id.tga")?;
+        assert_eq!(reloaded.image_id, id);
+        assert_eq!(reloaded.info.width, 128);
+    }
+    assert!(matches!(tga.set_image_id(&[0; 256]), Err(Error::ImageIdTooLong)));
+
+    Ok(())
+}
+
+fn test_into_luma16() -> Result<(), Error> {
+    write_raw_tga("example/images/temp_depth.tga", 3, 2, 1, 16, &[0x34, 0x12, 0xff, 0xff]);
+    assert_eq!(Tga::new("example/images/temp_depth.tga")?.into_luma16()?, [0x1234, 0xffff]);
+    write_raw_tga("example/images/temp_luma8.tga", 3, 2, 1, 8, &[0x00, 0x80]);
+    assert_eq!(Tga::new("example/images/temp_luma8.tga")?.into_luma16()?, [0x0000, 0x8080]);
+    assert!(Tga::new("example/images/UTC24.TGA")?.into_luma16().is_err());
+
+    Ok(())
+}
+
+fn test_typed_constructors() -> Result<(), Error> {
+    let tga = Tga::new_rgba8(2, 1, vec![1, 2, 3, 4, 5, 6, 7, 8])?;
+    assert_eq!(tga.info.pixel_format, TgaPixelFormat::ARGB32);
+    assert_eq!(saved_pixels(&tga, "example/images/temp_rgba8.tga")?, [3, 2, 1, 4, 7, 6, 5, 8]);
+    assert!(matches!(Tga::new_rgba8(2, 1, vec![0; 7]), Err(Error::InvalidImageDimensions)));
+
+    let tga = Tga::new_rgb8(1, 2, vec![1, 2, 3, 4, 5, 6])?;
+    assert_eq!(tga.info.pixel_format, TgaPixelFormat::RGB24);
+    assert_eq!(saved_pixels(&tga, "example/images/temp_rgb8.tga")?, [3, 2, 1, 6, 5, 4]);
+    assert!(matches!(Tga::new_rgb8(1, 2, vec![0; 8]), Err(Error::InvalidImageDimensions)));
+
+    Ok(())
+}
+
+fn test_map_alpha() -> Result<(), Error> {
+    let mut tga = Tga::new_rgba8(2, 1, vec![10, 20, 30, 200, 40, 50, 60, 101])?;
+    tga.map_alpha(|a| a / 2)?;
+    assert_eq!(saved_pixels(&tga, "example/images/temp_alpha.tga")?, [30, 20, 10, 100, 60, 50, 40, 50]);
+    assert!(Tga::new_rgb8(1, 1, vec![0; 3])?.map_alpha(|a| a).is_err());
+
+    Ok(())
+}
+
+fn test_threshold() -> Result<(), Error> {
+    let gradient: Vec<u8> = (0..=255).collect();
+    let mask = Tga::new_gray8(256, 1, gradient)?.threshold(100)?;
+    let pixels = saved_pixels(&mask, "example/images/temp_threshold.tga")?;
+    assert!(pixels[..100].iter().all(|v| *v == 0));
+    assert!(pixels[100..].iter().all(|v| *v == 255));
+
+    Ok(())
+}
+
+fn test_signed_distance_field() -> Result<(), Error> {
+    // 16x16 mask with a filled 4x4 square at (6, 6).
+    let mask: Vec<u8> = (0..256).map(|i| if (6..10).contains(&(i % 16)) && (6..10).contains(&(i / 16)) { 255 } else { 0 }).collect();
+    let sdf = Tga::new_gray8(16, 16, mask)?.signed_distance_field(4.0)?;
+    let field = saved_pixels(&sdf, "example/images/temp_sdf.tga")?;
+    let at = |x: usize, y: usize| field[y * 16 + x];
+    assert!(at(7, 7) > at(6, 7) && at(6, 7) > 128);
+    assert!(at(5, 7) < 128 && at(5, 7) > at(4, 7));
+    assert_eq!(at(0, 0), 0);
+
+    Ok(())
+}
+
+fn test_save_create_dirs() -> Result<(), Error> {
+    let _ = fs::remove_dir_all("example/images/temp_dirs");
+    let tga = Tga::new("example/images/UBW8.TGA")?;
+    assert!(tga.save("example/images/temp_dirs/a/b/out.tga").is_err());
+    tga.save_create_dirs("example/images/temp_dirs/a/b/out.tga")?;
+    assert!(fs::metadata("example/images/temp_dirs/a/b/out.tga")?.is_file());
+
+    Ok(())
+}
+
+fn test_rgb_equals() -> Result<(), Error> {
+    let a = Tga::new_rgba8(2, 1, vec![10, 20, 30, 255, 40, 50, 60, 255])?;
+    let b = Tga::new_rgba8(2, 1, vec![10, 20, 30, 0, 40, 50, 60, 128])?;
+    assert!(a.rgb_equals(&b)?);
+    assert!(a != b);
+    let c = Tga::new_rgb8(2, 1, vec![10, 20, 30, 40, 50, 60])?;
+    assert!(a.rgb_equals(&c)?);
+    let d = Tga::new_rgb8(2, 1, vec![10, 20, 30, 40, 50, 61])?;
+    assert!(!a.rgb_equals(&d)?);
+
+    Ok(())
+}
+
+fn test_row_checksums() -> Result<(), Error> {
+    let a = Tga::new_gray8(3, 3, vec![1, 2, 3, 4, 5, 6, 7, 8, 9])?;
+    let b = Tga::new_gray8(3, 3, vec![1, 2, 3, 4, 0, 6, 7, 8, 9])?;
+    let (a, b) = (a.row_checksums(), b.row_checksums());
+    assert_eq!(a.len(), 3);
+    assert_eq!(a[0], b[0]);
+    assert_ne!(a[1], b[1]);
+    assert_eq!(a[2], b[2]);
+    // CRC-32 check value of the IEEE polynomial.
+    assert_eq!(Tga::new_gray8(9, 1, b"123456789".to_vec())?.row_checksums(), [0xcbf43926]);
+
+    Ok(())
+}
+
+fn test_new_uninit() -> Result<(), Error> {
+    let mut tga = Tga::new_uninit(TgaInfo::for_format(4, 4, TgaPixelFormat::ARGB32))?;
+    assert_eq!(tga.pixels().len(), 4 * 4 * 4);
+    assert_eq!(saved_pixels(&tga, "example/images/temp_uninit.tga")?, [0; 64]);
+
+    // Filling the buffer writes in place, without reallocating it.
+    let buffer = tga.pixels().as_ptr();
+    let filled: Vec<u8> = (0..64).collect();
+    tga.pixels_mut().copy_from_slice(&filled);
+    assert!(tga.set_pixel_rgba(3, 3, [1, 2, 3, 4]));
+    assert_eq!(tga.pixels().as_ptr(), buffer);
+    assert_eq!(tga.pixels()[..60], filled[..60]);
+    assert_eq!(tga.pixels()[60..], [3, 2, 1, 4]);
+
+    tga.map_alpha(|_| 255)?;
+    assert!(tga.pixels().chunks(4).all(|px| px[3] == 255));
+    assert_eq!(tga.pixels().as_ptr(), buffer);
+    assert!(Tga::new_uninit(TgaInfo::for_format(0, 4, TgaPixelFormat::BW8)).is_err());
+
+    Ok(())
+}
+
+fn test_is_tga_v2() -> Result<(), Error> {
+    let mut v2 = fs::File::open("example/images/UTC24.TGA")?;
+    v2.seek(SeekFrom::Start(5))?;
+    assert!(is_tga_v2(&mut v2)?);
+    assert_eq!(v2.stream_position()?, 5);
+
+    let original = SaveOptions { footer: false };
+    Tga::new("example/images/UTC24.TGA")?.save_with_options("example/images/temp_v1.tga", &original)?;
+    let mut v1 = fs::File::open("example/images/temp_v1.tga")?;
+    assert!(!is_tga_v2(&mut v1)?);
+    assert_eq!(v1.stream_position()?, 0);
+
+    Ok(())
+}
+
+fn test_looks_like_tga() -> Result<(), Error> {
+    for entry in fs::read_dir("example/images")? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "TGA") {
+            assert!(looks_like_tga(&mut fs::File::open(path)?));
+        }
+    }
+
+    let mut seed: u32 = 12345;
+    let mut matches = 0;
+    for _ in 0..1000 {
+        let blob: Vec<u8> = (0..18).map(|_| {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 16) as u8
+        }).collect();
+        if looks_like_tga(&mut std::io::Cursor::new(blob)) {
+            matches += 1;
+        }
+    }
+    assert!(matches < 10);
+
+    Ok(())
+}
+
+fn test_data_offset() -> Result<(), Error> {
+    let mut tga = Tga::new("example/images/UTC24.TGA")?;
+    assert_eq!(tga.data_offset(), 18 + 26);
+    tga.set_image_id(&[])?;
+    assert_eq!(tga.data_offset(), 18);
+    write_unused_map_tga("example/images/temp_offset.tga");
+    assert_eq!(Tga::new("example/images/temp_offset.tga")?.data_offset(), 18 + 3 * 3);
+
+    Ok(())
+}
+
+fn test_sample() -> Result<(), Error> {
+    let tga = Tga::new_rgba8(2, 2, vec![255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255, 255, 255, 255])?;
+    assert_eq!(tga.sample(0.5, 0.5, WrapMode::Clamp), Some([128, 128, 128, 255]));
+    assert_eq!(tga.sample(0.25, 0.25, WrapMode::Clamp), Some([255, 0, 0, 255]));
+    assert_eq!(tga.sample(0.0, 0.25, WrapMode::Repeat), Some([128, 128, 0, 255]));
+    assert_eq!(tga.sample(1.25, 0.25, WrapMode::Repeat), Some([255, 0, 0, 255]));
+    assert_eq!(tga.sample(1.25, 0.25, WrapMode::Mirror), Some([0, 255, 0, 255]));
+    assert_eq!(tga.sample(f32::NAN, 0.0, WrapMode::Clamp), None);
+
+    Ok(())
+}
+
+fn test_tile() -> Result<(), Error> {
+    let tiled = Tga::new_gray8(2, 2, vec![1, 2, 3, 4])?.tile(4, 4)?;
+    assert_eq!((tiled.info.width, tiled.info.height), (4, 4));
+    assert_eq!(saved_pixels(&tiled, "example/images/temp_tile.tga")?,
+        [1, 2, 1, 2, 3, 4, 3, 4, 1, 2, 1, 2, 3, 4, 3, 4]);
+
+    Ok(())
+}
+
+fn assert_send<T: Send>() {}
+fn assert_sync<T: Sync>() {}
+
+fn test_send_sync() -> Result<(), Error> {
+    assert_send::<Tga>();
+    assert_sync::<Tga>();
+    let tga = Tga::new("example/images/UTC24.TGA")?;
+    let width = std::thread::spawn(move || tga.info.width).join().unwrap();
+    assert_eq!(width, 128);
+
+    Ok(())
+}
+
+fn test_vignette() -> Result<(), Error> {
+    let mut tga = Tga::new_rgba8(5, 5, [200, 200, 200, 77].repeat(25))?;
+    tga.vignette(0.8)?;
+    let pixels = saved_pixels(&tga, "example/images/temp_vignette.tga")?;
+    let (center, corner) = (&pixels[12 * 4..13 * 4], &pixels[..4]);
+    assert_eq!(center, [200, 200, 200, 77]);
+    assert!(corner[0] < center[0] && corner[3] == 77);
+
+    Ok(())
+}
+
+fn test_suppress_spill() -> Result<(), Error> {
+    let pixels = vec![120, 180, 110, 100, 100, 100, 180, 100, 180];
+    let mut tga = Tga::new_rgb8(3, 1, pixels)?;
+    tga.suppress_spill([0, 255, 0], 1.0)?;
+    let pixels = saved_pixels(&tga, "example/images/temp_spill.tga")?;
+    // Stored as BGR: the green tint is gone, gray and magenta are untouched.
+    assert!(pixels[1] < 180 && pixels[1] <= pixels[0].max(pixels[2]));
+    assert_eq!(pixels[3..], [100, 100, 100, 180, 100, 180]);
+    assert!(Tga::new_gray8(1, 1, vec![0])?.suppress_spill([0, 255, 0], 1.0).is_err());
+
+    Ok(())
+}
+
+fn test_quantize() -> Result<(), Error> {
+    let gradient: Vec<u8> = (0..=255u8).flat_map(|v| [v, v / 2, 255 - v]).collect();
+    let tga = Tga::new_rgb8(256, 1, gradient)?;
+    let indexed = tga.quantize(16)?;
+    assert_eq!(indexed.palette_len(), 16);
+    assert_eq!(indexed.palette_format, TgaPixelFormat::RGB24);
+    assert!(indexed.expand()?.approx_eq(&tga, 8));
+    assert!(matches!(tga.quantize(257), Err(Error::InvalidPaletteSize)));
+
+    Ok(())
+}
+
+fn test_convolve() -> Result<(), Error> {
+    let pixels = vec![50, 50, 50, 50, 60, 50, 50, 50, 50];
+    let mut tga = Tga::new_gray8(3, 3, pixels.clone())?;
+    tga.convolve(&[0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0], 3, 3, 1.0, 0.0)?;
+    assert_eq!(saved_pixels(&tga, "example/images/temp_convolve.tga")?, pixels);
+
+    tga.convolve(&[0.0, -1.0, 0.0, -1.0, 5.0, -1.0, 0.0, -1.0, 0.0], 3, 3, 1.0, 0.0)?;
+    let sharpened = saved_pixels(&tga, "example/images/temp_convolve.tga")?;
+    assert_eq!(sharpened[4], 100);
+    assert_eq!(sharpened[1], 40);
+    assert!(matches!(tga.convolve(&[1.0; 4], 2, 2, 1.0, 0.0), Err(Error::InvalidKernel)));
+
+    Ok(())
+}
+
+fn test_compact_palette() -> Result<(), Error> {
+    let palette: Vec<u8> = (0..=255u8).flat_map(|v| [v, v, v]).collect();
+    let mut indexed = IndexedTga {
+        width: 2,
+        height: 2,
+        palette_format: TgaPixelFormat::RGB24,
+        palette,
+        indices: vec![200, 7, 42, 7],
+    };
+    let expanded = indexed.expand()?;
+    assert_eq!(indexed.used_colors(), [7, 42, 200]);
+    indexed.compact_palette();
+    assert_eq!(indexed.palette_len(), 3);
+    assert_eq!(indexed.indices, [2, 0, 1, 0]);
+    assert!(indexed.expand()? == expanded);
+
+    Ok(())
+}
+
+fn test_origin_bits() -> Result<(), Error> {
+    let expected: Vec<u8> = (1..=9).collect();
+    for descriptor in [0x00u8, 0x10, 0x20, 0x30] {
+        // Store the rows and columns in the order the origin bits declare.
+        let mut stored = Vec::new();
+        for y in 0..3 {
+            let y = if descriptor & 0x20 != 0 { y } else { 2 - y };
+            for x in 0..3 {
+                let x = if descriptor & 0x10 != 0 { 2 - x } else { x };
+                stored.push(expected[y * 3 + x]);
+            }
+        }
+        write_raw_tga("example/images/temp_origin.tga", 3, 3, 3, 8, &stored);
+        let mut bytes = fs::read("example/images/temp_origin.tga")?;
+        bytes[17] = descriptor;
+        fs::write("example/images/temp_origin.tga", bytes)?;
+
+        let tga = Tga::new("example/images/temp_origin.tga")?;
+        assert_eq!(saved_pixels(&tga, "example/images/temp_origin_out.tga")?, expected, "descriptor {:#x}", descriptor);
+    }
+
+    Ok(())
+}
+
+fn test_memory_usage() -> Result<(), Error> {
+    let tga = Tga::new("example/images/UTC24.TGA")?;
+    assert_eq!(tga.memory_usage(), 128 * 128 * 3 + 26);
+    let mut tga = Tga::new_gray8(4, 2, vec![0; 8])?;
+    assert_eq!(tga.memory_usage(), 8);
+    tga.set_image_id(b"id")?;
+    assert_eq!(tga.memory_usage(), 10);
+
+    Ok(())
+}
+
+fn test_rle_packet_count() -> Result<(), Error> {
+    // 2x2 grayscale images made of a single run-length packet, crossing the scanlines.
+    let continuous = DecodeOptions { rle_mode: RleMode::Continuous, ..DecodeOptions::default() };
+    write_raw_tga("example/images/temp_rle_exact.tga", 11, 2, 2, 8, &[0x83, 9]);
+    assert!(Tga::new_with_options("example/images/temp_rle_exact.tga", &continuous).is_ok());
+    write_raw_tga("example/images/temp_rle_under.tga", 11, 2, 2, 8, &[0x82, 9]);
+    assert!(matches!(Tga::new_with_options("example/images/temp_rle_under.tga", &continuous), Err(Error::IllegalHeader)));
+    write_raw_tga("example/images/temp_rle_over.tga", 11, 2, 2, 8, &[0x84, 9]);
+    assert!(matches!(Tga::new_with_options("example/images/temp_rle_over.tga", &continuous), Err(Error::IllegalHeader)));
+
+    Ok(())
+}
+
+fn test_compose_over_premultiplied() -> Result<(), Error> {
+    let bottom = vec![200, 100, 50, 255, 10, 20, 30, 128, 0, 0, 0, 0, 90, 180, 250, 64];
+    let top = vec![0, 0, 255, 128, 255, 255, 255, 255, 60, 70, 80, 200, 1, 2, 3, 0];
+
+    let mut straight = Tga::new_rgba8(2, 2, bottom.clone())?;
+    straight.compose_over(&Tga::new_rgba8(2, 2, top.clone())?)?;
+    straight.premultiply_alpha()?;
+
+    let mut premultiplied = Tga::new_rgba8(2, 2, bottom)?;
+    let mut layer = Tga::new_rgba8(2, 2, top)?;
+    premultiplied.premultiply_alpha()?;
+    assert!(premultiplied.compose_over_premultiplied(&layer).is_err());
+    layer.premultiply_alpha()?;
+    premultiplied.compose_over_premultiplied(&layer)?;
+    assert!(premultiplied.approx_eq(&straight, 2));
+
+    Ok(())
+}
+
+fn test_duplicate_row_count() -> Result<(), Error> {
+    let stripes: Vec<u8> = (0..4 * 5).map(|i| if i % 2 == 0 { 255 } else { 0 }).collect();
+    assert_eq!(Tga::new_gray8(4, 5, stripes)?.duplicate_row_count(), 4);
+    let mut letterbox = vec![0u8; 4 * 5];
+    letterbox[8..12].copy_from_slice(&[1, 2, 3, 4]);
+    assert_eq!(Tga::new_gray8(4, 5, letterbox)?.duplicate_row_count(), 2);
+
+    Ok(())
+}
+
+fn test_to_cursor() -> Result<(), Error> {
+    use std::io::Read;
+
+    let tga = Tga::new("example/images/UTC32.TGA")?;
+    let mut cursor = tga.to_cursor()?;
+    assert_eq!(cursor.position(), 0);
+    let mut bytes = Vec::new();
+    cursor.read_to_end(&mut bytes)?;
+    tga.save("example/images/temp_cursor.tga")?;
+    assert_eq!(bytes, fs::read("example/images/temp_cursor.tga")?);
+    assert!(Tga::new("example/images/temp_cursor.tga")? == tga);
+
+    cursor.rewind()?;
+    let decoded = Tga::from_reader(&mut cursor)?;
+    assert_eq!(decoded.dimensions(), tga.dimensions());
+    assert_eq!(decoded.info, tga.info);
+    assert_eq!(decoded.pixels(), tga.pixels());
+
+    Ok(())
+}
+
+fn test_reinterpret_format() -> Result<(), Error> {
+    let mut tga = Tga::new("example/images/UTC16.TGA")?;
+    let pixels = saved_pixels(&tga, "example/images/temp_reinterpret.tga")?;
+    tga.reinterpret_format(TgaPixelFormat::BW16)?;
+    assert_eq!(tga.header.get_pixel_format().unwrap(), TgaPixelFormat::BW16);
+    assert_eq!(saved_pixels(&tga, "example/images/temp_reinterpret.tga")?, pixels);
+    assert!(matches!(tga.reinterpret_format(TgaPixelFormat::RGB24), Err(Error::UnsupportedPixelFormat)));
+
+    let mut tga = Tga::new_rgb8(1, 1, vec![1, 2, 3])?;
+    assert!(tga.reinterpret_format(TgaPixelFormat::BW8).is_err());
+    assert_eq!(tga.info.pixel_format, TgaPixelFormat::RGB24);
+
+    Ok(())
+}
+
+#[cfg(feature = "png")]
+fn test_save_png() -> Result<(), Error> {
+    let tga = Tga::new("example/images/UTC32.TGA")?;
+    tga.save_png("example/images/temp_utc32.png")?;
+    let png = fs::read("example/images/temp_utc32.png")?;
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    assert_eq!(&png[12..16], b"IHDR");
+    assert_eq!(png[16..24], [0, 0, 0, 128, 0, 0, 0, 128]);
+    // 8 bits RGBA.
+    assert_eq!(png[24..26], [8, 6]);
+
+    let loaded = Tga::from_png("example/images/temp_utc32.png")?;
+    assert_eq!(loaded.dimensions(), tga.dimensions());
+    assert_eq!(loaded.get_pixel_rgba(0, 0), tga.get_pixel_rgba(0, 0));
+    assert_eq!(loaded.get_pixel_rgba(127, 127), tga.get_pixel_rgba(127, 127));
+    assert_eq!(loaded.pixels(), tga.pixels());
+
+    Ok(())
+}
+
+#[cfg(feature = "png")]
+fn test_from_png() -> Result<(), Error> {
+    let tga = Tga::new("example/images/UTC32.TGA")?;
+    tga.save_png("example/images/temp_utc32.png")?;
+    assert!(Tga::from_png("example/images/temp_utc32.png")?.approx_eq(&tga, 0));
+
+    let gray = Tga::new_gray8(3, 2, vec![0, 50, 100, 150, 200, 250])?;
+    gray.save_png("example/images/temp_gray.png")?;
+    assert!(Tga::from_png("example/images/temp_gray.png")?.approx_eq(&gray, 0));
+
+    // Deflate compressed with dynamic Huffman codes and all five scanline filters.
+    let png = Tga::from_png("example/images/UTC24.PNG")?;
+    assert_eq!(png.info.pixel_format, TgaPixelFormat::RGB24);
+    assert!(png.approx_eq(&Tga::new("example/images/UTC24.TGA")?, 0));
+
+    Ok(())
+}
+
+#[cfg(feature = "png")]
+fn test_png_limits() -> Result<(), Error> {
+    let gray = Tga::new_gray8(4, 4, vec![0; 16])?;
+    gray.save_png("example/images/temp_gray_limits.png")?;
+    let options = DecodeOptions { max_pixels: Some(15), ..DecodeOptions::default() };
+    assert!(matches!(
+        Tga::from_png_with_options("example/images/temp_gray_limits.png", &options),
+        Err(Error::ImageTooLarge)
+    ));
+    let options = DecodeOptions { max_pixels: Some(16), ..DecodeOptions::default() };
+    assert!(Tga::from_png_with_options("example/images/temp_gray_limits.png", &options).is_ok());
+
+    // Chunks whose CRC doesn't match their data are rejected: a patched IHDR height, then
+    // a damaged byte of compressed data.
+    let original = fs::read("example/images/UTC24.PNG")?;
+    let mut png = original.clone();
+    png[20..24].copy_from_slice(&1u32.to_be_bytes());
+    fs::write("example/images/temp_bad_crc.png", &png)?;
+    assert!(matches!(Tga::from_png("example/images/temp_bad_crc.png"), Err(Error::IllegalHeader)));
+    let mut png = original.clone();
+    let idat = png.windows(4).position(|w| w == b"IDAT").unwrap();
+    png[idat + 10] ^= 0x01;
+    fs::write("example/images/temp_bad_crc.png", &png)?;
+    assert!(matches!(Tga::from_png("example/images/temp_bad_crc.png"), Err(Error::IllegalHeader)));
+
+    Ok(())
+}
+
+#[cfg(feature = "image")]
+fn test_dynamic_image() -> Result<(), Error> {
+    use image::DynamicImage;
+
+    for name in ["UBW8", "UTC16", "UTC24", "UTC32"] {
+        let tga = Tga::new(&format!("example/images/{}.TGA", name))?;
+        let color = match tga.info.pixel_format {
+            TgaPixelFormat::BW8 => image::ColorType::L8,
+            TgaPixelFormat::RGB24 => image::ColorType::Rgb8,
+            TgaPixelFormat::RGB555 if !tga.info.has_alpha() => image::ColorType::Rgb8,
+            _ => image::ColorType::Rgba8,
+        };
+        let dynamic = DynamicImage::try_from(&tga)?;
+        assert_eq!(dynamic.color(), color);
+        assert_eq!((dynamic.width(), dynamic.height()), (128, 128));
+        let [r, g, b, a] = tga.get_pixel_rgba(5, 7).unwrap();
+        let expected = match color {
+            image::ColorType::L8 => [r, r, r, 255],
+            _ => [r, g, b, a],
+        };
+        assert_eq!(image::GenericImageView::get_pixel(&dynamic, 5, 7).0, expected);
+
+        let back = Tga::from_dynamic_image(&dynamic)?;
+        assert!(back.approx_eq(&tga, 0));
+    }
+
+    let gray16 = Tga::from_raw(2, 1, TgaPixelFormat::BW16, &[0x34, 0x12, 0xff, 0xff])?;
+    let dynamic = DynamicImage::try_from(&gray16)?;
+    assert_eq!(dynamic.as_luma16().unwrap().as_raw(), &[0x1234, 0xffff]);
+    assert_eq!(Tga::from_dynamic_image(&dynamic)?, gray16);
+
+    let mut swapped = Tga::new("example/images/UTC24.TGA")?;
+    swapped.to_rgba()?;
+    assert!(matches!(DynamicImage::try_from(&swapped), Err(Error::UnsupportedPixelFormat)));
+    let wide = DynamicImage::new_rgb8(65536, 1);
+    assert!(matches!(Tga::from_dynamic_image(&wide), Err(Error::InvalidImageDimensions)));
+
+    Ok(())
+}
+
+fn test_rotate90() -> Result<(), Error> {
+    let mut tga = Tga::new_gray8(3, 2, vec![1, 2, 3, 4, 5, 6])?;
+    tga.rotate90()?;
+    assert_eq!((tga.info.width, tga.info.height), (2, 3));
+    assert_eq!((tga.header.image_width, tga.header.image_height), (2, 3));
+    assert!(tga.validate_against_header().is_ok());
+    assert_eq!(saved_pixels(&tga, "example/images/temp_rotate90.tga")?, [4, 1, 5, 2, 6, 3]);
+
+    let loaded = Tga::new("example/images/temp_rotate90.tga")?;
+    assert_eq!((loaded.info.width, loaded.info.height), (2, 3));
+    assert!(loaded.approx_eq(&tga, 0));
+
+    tga.header.image_width = 3;
+    assert!(matches!(tga.validate_against_header(), Err(Error::IllegalHeader)));
+
+    Ok(())
+}
+
+fn test_dimensions_survive_transforms() -> Result<(), Error> {
+    let pixels: Vec<u8> = (0..4 * 3 * 3).collect();
+    let mut tga = Tga::new_rgb8(4, 3, pixels)?;
+    tga.rotate90()?;
+    tga.image_flip_h()?;
+    assert_eq!(tga.dimensions(), (3, 4));
+    assert_eq!((tga.header.image_width, tga.header.image_height), (3, 4));
+
+    tga.save("example/images/temp_dimensions.tga")?;
+    let loaded = Tga::new("example/images/temp_dimensions.tga")?;
+    assert_eq!(loaded.dimensions(), (3, 4));
+    assert!(loaded.validate_against_header().is_ok());
+    assert!(loaded.approx_eq(&tga, 0));
+
+    tga.save_rle("example/images/temp_dimensions_rle.tga", RleMode::PerScanline)?;
+    assert!(Tga::new("example/images/temp_dimensions_rle.tga")?.approx_eq(&tga, 0));
+
+    Ok(())
+}
+
+fn test_crop_to_aspect() -> Result<(), Error> {
+    let pixels: Vec<u8> = (0..16 * 9).map(|i| (i % 16) as u8).collect();
+    let tga = Tga::new_gray8(16, 9, pixels)?;
+    let square = tga.crop_to_aspect(1, 1, Anchor::Center)?;
+    assert_eq!(square.dimensions(), (9, 9));
+    // Centered: the 7 extra columns are split as 3 on the left and 4 on the right.
+    let row: Vec<u8> = (3..12).collect();
+    assert_eq!(saved_pixels(&square, "example/images/temp_aspect.tga")?, row.repeat(9));
+
+    let right = tga.crop_to_aspect(1, 1, Anchor::TopRight)?;
+    assert_eq!(saved_pixels(&right, "example/images/temp_aspect.tga")?[..9], (7..16).collect::<Vec<u8>>());
+    assert_eq!(tga.crop_to_aspect(16, 3, Anchor::Top)?.dimensions(), (16, 3));
+    assert!(tga.crop_to_aspect(0, 1, Anchor::Center).is_err());
+
+    Ok(())
+}
+
+fn test_cycle_palette() -> Result<(), Error> {
+    let mut indexed = IndexedTga {
+        width: 4,
+        height: 1,
+        palette_format: TgaPixelFormat::RGB24,
+        palette: vec![0, 0, 0, 10, 10, 10, 20, 20, 20, 30, 30, 30],
+        indices: vec![0, 1, 2, 3],
+    };
+    let frames = indexed.cycle_frames(1, 3, 3)?;
+    assert_eq!(frames.len(), 3);
+    assert_eq!(saved_pixels(&frames[1], "example/images/temp_cycle.tga")?, [0, 0, 0, 30, 30, 30, 10, 10, 10, 20, 20, 20]);
+
+    indexed.cycle_palette(1, 3, 1)?;
+    assert!(indexed.expand()?.approx_eq(&frames[1], 0));
+    indexed.cycle_palette(1, 3, -1)?;
+    assert!(indexed.expand()?.approx_eq(&frames[0], 0));
+    assert!(matches!(indexed.cycle_palette(2, 3, 1), Err(Error::InvalidPaletteSize)));
+
+    Ok(())
+}
+
+fn test_count_matching() -> Result<(), Error> {
+    let pixels = [[1, 2, 3, 255], [4, 5, 6, 0], [7, 8, 9, 255], [1, 1, 1, 128]].concat();
+    let tga = Tga::new_rgba8(2, 2, pixels)?;
+    assert_eq!(tga.count_matching(|px| px[3] == 255), 2);
+    assert_eq!(tga.count_matching(|px| px[3] == 0), 1);
+    // Stored as BGRA.
+    assert_eq!(tga.count_matching(|px| px[2] >= 4), 2);
+
+    Ok(())
+}
+
+fn test_flood_fill() -> Result<(), Error> {
+    // A wall of 9 splits the image, the diagonal doesn't connect the regions.
+    let pixels = vec![
+        0, 0, 9, 0,
+        0, 9, 0, 0,
+        9, 0, 0, 0,
+    ];
+    let mut tga = Tga::new_gray8(4, 3, pixels)?;
+    assert_eq!(tga.flood_fill(0, 0, &[5])?, 3);
+    assert_eq!(saved_pixels(&tga, "example/images/temp_fill.tga")?, [5, 5, 9, 0, 5, 9, 0, 0, 9, 0, 0, 0]);
+    assert_eq!(tga.flood_fill(3, 2, &[7])?, 6);
+    assert!(matches!(tga.flood_fill(4, 0, &[7]), Err(Error::InvalidImageDimensions)));
+    assert!(matches!(tga.flood_fill(0, 0, &[7, 7]), Err(Error::UnsupportedPixelFormat)));
+
+    let mut large = Tga::new_gray8(1024, 1024, vec![0; 1024 * 1024])?;
+    assert_eq!(large.flood_fill(512, 512, &[1])?, 1024 * 1024);
+
+    Ok(())
+}
+
+fn test_indexed_grayscale() -> Result<(), Error> {
+    let tga = Tga::new("example/images/UBW8.TGA")?;
+    let indexed = tga.to_indexed_grayscale()?;
+    assert_eq!(indexed.palette_len(), 256);
+    indexed.save("example/images/temp_indexed_gray.tga")?;
+    let bytes = fs::read("example/images/temp_indexed_gray.tga")?;
+    assert_eq!(bytes.len(), 18 + 256 * 3 + 128 * 128 + 26);
+    assert_eq!(&bytes[bytes.len() - 18..], b"TRUEVISION-XFILE.\0");
+
+    let loaded = IndexedTga::load("example/images/temp_indexed_gray.tga")?;
+    assert_eq!(loaded, indexed);
+    indexed.save_with_options("example/images/temp_indexed_gray.tga", &SaveOptions { footer: false })?;
+    assert_eq!(fs::metadata("example/images/temp_indexed_gray.tga")?.len(), 18 + 256 * 3 + 128 * 128);
+    assert!(loaded.expand()?.approx_eq(&tga, 0));
+    assert!(matches!(Tga::new_rgb8(1, 1, vec![0; 3])?.to_indexed_grayscale(), Err(Error::UnsupportedPixelFormat)));
+
+    let ucm8 = IndexedTga::load("example/images/UCM8.TGA")?;
+    assert_eq!((ucm8.width, ucm8.height, ucm8.palette_len()), (128, 128, 256));
+    assert_eq!(ucm8.palette_format, TgaPixelFormat::RGB555);
+
+    Ok(())
+}
+
+fn test_luminance_at() -> Result<(), Error> {
+    let tga = Tga::new_rgb8(2, 1, vec![200, 100, 50, 255, 255, 255])?;
+    // 0.299 * 200 + 0.587 * 100 + 0.114 * 50 = 124.2
+    assert_eq!(tga.luminance_at(0, 0), Some(124));
+    assert_eq!(tga.luminance_at(1, 0), Some(255));
+    assert_eq!(tga.luminance_at(2, 0), None);
+    assert_eq!(Tga::new_gray8(1, 1, vec![77])?.luminance_at(0, 0), Some(77));
+
+    Ok(())
+}
+
+fn test_from_bytes() -> Result<(), Error> {
+    for name in ["UTC24", "CTC24", "UBW8"] {
+        let path = format!("example/images/{}.TGA", name);
+        assert_eq!(Tga::from_bytes(&fs::read(&path)?)?, Tga::new(&path)?);
+    }
+
+    let embedded = Tga::from_bytes(include_bytes!("images/UTC32.TGA"))?;
+    assert_eq!(embedded.dimensions(), (128, 128));
+    assert!(Tga::from_bytes(&include_bytes!("images/UTC32.TGA")[..100]).is_err());
+    assert!(Tga::from_bytes(&[]).is_err());
+
+    Ok(())
+}
+
+fn test_set_palette_entry() -> Result<(), Error> {
+    let mut indexed = IndexedTga {
+        width: 3,
+        height: 2,
+        palette_format: TgaPixelFormat::RGB24,
+        palette: vec![0, 0, 0, 10, 20, 30],
+        indices: vec![0, 1, 1, 0, 1, 0],
+    };
+    indexed.set_palette_entry(1, &[1, 2, 3])?;
+    let pixels = saved_pixels(&indexed.expand()?, "example/images/temp_palette_entry.tga")?;
+    for (px, index) in pixels.chunks_exact(3).zip(&indexed.indices) {
+        assert_eq!(px, if *index == 1 { [1, 2, 3] } else { [0, 0, 0] });
+    }
+    assert!(matches!(indexed.set_palette_entry(2, &[1, 2, 3]), Err(Error::ColorMapIndexFailed)));
+    assert!(matches!(indexed.set_palette_entry(0, &[1, 2]), Err(Error::UnsupportedPixelFormat)));
+
+    Ok(())
+}
+
+fn test_from_reader() -> Result<(), Error> {
+    let expected = Tga::new("example/images/CTC32.TGA")?;
+    let mut reader = std::io::BufReader::new(fs::File::open("example/images/CTC32.TGA")?);
+    assert_eq!(Tga::from_reader(&mut reader)?, expected);
+
+    let mut cursor = expected.to_cursor()?;
+    assert_eq!(Tga::from_reader(&mut cursor)?, expected);
+
+    // The image may start after other data.
+    let mut bytes = b"prefix".to_vec();
+    bytes.extend(fs::read("example/images/CTC32.TGA")?);
+    let mut cursor = std::io::Cursor::new(bytes);
+    cursor.seek(SeekFrom::Start(6))?;
+    let strict = DecodeOptions { rle_mode: RleMode::PerScanline, ..DecodeOptions::default() };
+    assert_eq!(Tga::from_reader_with_options(&mut cursor, &strict)?, expected);
+    cursor.seek(SeekFrom::Start(0))?;
+    assert!(TgaHeader::from_file(&mut cursor).is_err());
+
+    Ok(())
+}
+
+fn test_thumbnail_fit() -> Result<(), Error> {
+    let tga = Tga::new_rgb8(100, 40, vec![128; 100 * 40 * 3])?;
+    for filter in [ScaleFilter::Nearest, ScaleFilter::Bilinear] {
+        let thumbnail = tga.thumbnail_fit(50, filter)?;
+        assert_eq!(thumbnail.dimensions(), (50, 20));
+        assert_eq!(thumbnail.count_matching(|px| px == [128, 128, 128]), 50 * 20);
+    }
+
+    let tall = Tga::new_gray8(3, 300, vec![0; 900])?;
+    assert_eq!(tall.thumbnail_fit(30, ScaleFilter::Nearest)?.dimensions(), (1, 30));
+    assert!(matches!(tall.thumbnail_fit(0, ScaleFilter::Nearest), Err(Error::InvalidImageDimensions)));
+
+    Ok(())
+}
+
+fn test_write_to() -> Result<(), Error> {
+    let tga = Tga::new("example/images/UTC24.TGA")?;
+    let mut buf = Vec::new();
+    tga.write_to(&mut buf)?;
+    assert_eq!(buf, tga.to_vec()?);
+    assert_eq!(Tga::from_bytes(&buf)?, tga);
+
+    let mut writer = std::io::BufWriter::new(fs::File::create("example/images/temp_write_to.tga")?);
+    tga.write_to(&mut writer)?;
+    drop(writer);
+    assert_eq!(fs::read("example/images/temp_write_to.tga")?, buf);
+
+    // A sink too small fails instead of silently dropping bytes.
+    let mut small = [0u8; 100];
+    assert!(matches!(tga.write_to(&mut &mut small[..]), Err(Error::IOError(_))));
+
+    Ok(())
+}
+
+fn test_alpha_from_luminance() -> Result<(), Error> {
+    let gradient: Vec<u8> = (0..=255u8).flat_map(|v| [v, v / 2, 255 - v]).collect();
+    let tga = Tga::new_rgb8(256, 1, gradient)?;
+    let matte = tga.alpha_from_luminance()?;
+    assert_eq!(matte.info.pixel_format, TgaPixelFormat::ARGB32);
+    assert!(matte.rgb_equals(&tga)?);
+    let pixels = saved_pixels(&matte, "example/images/temp_matte.tga")?;
+    for (x, px) in pixels.chunks_exact(4).enumerate() {
+        assert_eq!(Some(px[3]), tga.luminance_at(x as u16, 0));
+    }
+    assert!(pixels[3] < pixels[255 * 4 + 3]);
+
+    Ok(())
+}
+
+fn test_pixels() -> Result<(), Error> {
+    let mut tga = Tga::new("example/images/UTC24.TGA")?;
+    assert_eq!(tga.pixels().len(), 128 * 128 * 3);
+    assert_eq!(tga.pixels(), saved_pixels(&tga, "example/images/temp_pixels.tga")?);
+
+    tga.pixels_mut()[..3].copy_from_slice(&[1, 2, 3]);
+    assert_eq!(saved_pixels(&tga, "example/images/temp_pixels.tga")?[..3], [1, 2, 3]);
+
+    Ok(())
+}
+
+fn test_save_with_crc() -> Result<(), Error> {
+    let tga = Tga::new("example/images/UTC24.TGA")?;
+    tga.save_with_crc("example/images/temp_crc.tga")?;
+    let verify = DecodeOptions { verify_crc: true, ..DecodeOptions::default() };
+    let loaded = Tga::new_with_options("example/images/temp_crc.tga", &verify)?;
+    assert_eq!(loaded, tga);
+    assert!(is_tga_v2(&mut fs::File::open("example/images/temp_crc.tga")?)?);
+
+    // Flip one pixel bit.
+    let mut bytes = fs::read("example/images/temp_crc.tga")?;
+    bytes[18 + 26 + 1000] ^= 0x01;
+    fs::write("example/images/temp_crc_bad.tga", &bytes)?;
+    assert!(matches!(Tga::new_with_options("example/images/temp_crc_bad.tga", &verify), Err(Error::ChecksumMismatch)));
+    // Without verification the corruption goes unnoticed.
+    assert!(Tga::new("example/images/temp_crc_bad.tga").is_ok());
+    // Files without a CRC have nothing to verify.
+    assert!(Tga::new_with_options("example/images/UTC24.TGA", &verify).is_ok());
+
+    Ok(())
+}
+
+fn test_flip_v_wide() -> Result<(), Error> {
+    // Half the width is more than half the height: the rows used to be swapped twice.
+    let mut tga = Tga::new_gray8(4, 2, vec![0, 0, 0, 0, 1, 1, 1, 1])?;
+    tga.image_flip_v()?;
+    assert_eq!(tga.pixels(), [1, 1, 1, 1, 0, 0, 0, 0]);
+
+    // A bottom-left origin file of the same shape loads the right way up.
+    write_raw_tga("example/images/temp_wide.tga", 3, 4, 2, 8, &[0, 0, 0, 0, 1, 1, 1, 1]);
+    let mut bytes = fs::read("example/images/temp_wide.tga")?;
+    bytes[17] = 0;
+    fs::write("example/images/temp_wide.tga", &bytes)?;
+    assert_eq!(Tga::new("example/images/temp_wide.tga")?.pixels(), [1, 1, 1, 1, 0, 0, 0, 0]);
+
+    Ok(())
+}
+
+fn test_texture_descriptor() -> Result<(), Error> {
+    let tga = Tga::new_rgba8(3, 2, vec![7; 3 * 2 * 4])?;
+    let texture = tga.texture_descriptor()?;
+    assert_eq!((texture.width, texture.height), (3, 2));
+    // ARGB32 pixels are stored as B, G, R, A bytes.
+    assert_eq!(texture.format, GpuFormat::Bgra8);
+    assert_eq!(texture.row_pitch, 12);
+    assert_eq!(texture.data.len(), texture.row_pitch * 2);
+
+    let gray = Tga::new_gray8(5, 1, vec![0; 5])?;
+    assert_eq!(gray.texture_descriptor()?.format, GpuFormat::R8);
+    assert_eq!(gray.texture_descriptor()?.row_pitch, 5);
+
+    Ok(())
+}
+
+fn test_flip_non_square() -> Result<(), Error> {
+    // Rows below the width used to be clamped onto the last row.
+    let mut tall = Tga::new_gray8(2, 4, vec![0, 0, 1, 1, 2, 2, 3, 3])?;
+    tall.image_flip_v()?;
+    assert_eq!(tall.pixels(), [3, 3, 2, 2, 1, 1, 0, 0]);
+    tall.image_flip_h()?;
+    assert_eq!(tall.pixels(), [3, 3, 2, 2, 1, 1, 0, 0]);
+
+    let pattern: Vec<u8> = (0..16).collect();
+    let mut wide = Tga::new_gray8(8, 2, pattern.clone())?;
+    wide.image_flip_h()?;
+    wide.image_flip_v()?;
+    let expected: Vec<u8> = pattern.iter().rev().copied().collect();
+    assert_eq!(wide.pixels(), expected);
+
+    Ok(())
+}
+
+fn test_decode_out_of_memory() -> Result<(), Error> {
+    // A 65535x65535 ARGB32 header asks for about 17 GB of pixels, which the allocator
+    // refuses: the error is returned rather than aborting.
+    let mut bytes = vec![0u8; 18];
+    bytes[2] = 2;
+    bytes[12..16].copy_from_slice(&[0xff, 0xff, 0xff, 0xff]);
+    bytes[16] = 32;
+    bytes[17] = 0x28;
+    let options = DecodeOptions { max_pixels: None, ..DecodeOptions::default() };
+    let decoded = Tga::from_reader_with_options(&mut std::io::Cursor::new(&bytes), &options);
+    assert!(matches!(decoded, Err(Error::ErrorOutOfMemory)));
+    assert!(matches!(Tga::from_bytes(&bytes), Err(Error::ImageTooLarge)));
+
+    Ok(())
+}
+
+fn test_dimension_mismatch() -> Result<(), Error> {
+    let mut bottom = Tga::new_rgba8(4, 3, vec![0; 4 * 3 * 4])?;
+    let top = Tga::new_rgba8(2, 5, vec![0; 2 * 5 * 4])?;
+    assert!(matches!(
+        bottom.compose_over(&top),
+        Err(Error::DimensionMismatch { expected: (4, 3), got: (2, 5) })
+    ));
+    assert!(matches!(bottom.rgb_equals(&top), Err(Error::DimensionMismatch { .. })));
+    assert!(bottom.ensure_same_dimensions(&bottom.tile(4, 3)?).is_ok());
+
+    let gray = Tga::new_gray8(1, 1, vec![0])?;
+    let other = Tga::new_gray8(1, 2, vec![0, 0])?;
+    assert!(matches!(
+        Tga::pack_channels(&gray, &gray, &other, None),
+        Err(Error::DimensionMismatch { expected: (1, 1), got: (1, 2) })
+    ));
+
+    Ok(())
+}
+
+fn test_into_vec() -> Result<(), Error> {
+    let tga = Tga::new("example/images/UTC32.TGA")?;
+    let expected = tga.pixels().to_vec();
+    let pixels = tga.into_vec();
+    assert_eq!(pixels.len(), 128 * 128 * 4);
+    assert_eq!(pixels, expected);
+
+    Ok(())
+}
+
+fn test_save_chunked() -> Result<(), Error> {
+    let tga = Tga::new("example/images/UTC24.TGA")?;
+    let mut reports = Vec::new();
+    tga.save_chunked("example/images/temp_chunked.tga", 1000, |written| reports.push(written))?;
+    let expected = tga.to_vec()?;
+    assert_eq!(fs::read("example/images/temp_chunked.tga")?, expected);
+    assert_eq!(reports.first(), Some(&(18 + 26)));
+    assert_eq!(reports.last(), Some(&expected.len()));
+    assert!(reports.windows(2).all(|pair| pair[1] - pair[0] <= 1000));
+
+    Ok(())
+}
+
+fn test_from_raw() -> Result<(), Error> {
+    // A 2x2 BGR555 checkerboard of red and blue.
+    let (red, blue) = (0x7c00u16.to_le_bytes(), 0x001fu16.to_le_bytes());
+    let pixels = [red, blue, blue, red].concat();
+    let tga = Tga::from_raw(2, 2, TgaPixelFormat::RGB555, &pixels)?;
+    assert_eq!(tga.header.pixel_depth, 16);
+    assert!(tga.validate_against_header().is_ok());
+    tga.save("example/images/temp_raw.tga")?;
+    assert_eq!(Tga::new("example/images/temp_raw.tga")?, tga);
+
+    assert!(matches!(Tga::from_raw(2, 2, TgaPixelFormat::RGB555, &pixels[1..]), Err(Error::InvalidImageDimensions)));
+    assert!(matches!(Tga::from_raw(0, 2, TgaPixelFormat::BW8, &[]), Err(Error::InvalidImageDimensions)));
+
+    Ok(())
+}
+
+fn test_aspect_ratio() -> Result<(), Error> {
+    let wide = Tga::new_gray8(16, 9, vec![0; 16 * 9])?;
+    assert!(!wide.is_square());
+    assert!((wide.aspect_ratio() - 16.0 / 9.0).abs() < 1e-6);
+
+    let square = Tga::new_gray8(64, 64, vec![0; 64 * 64])?;
+    assert!(square.is_square());
+    assert_eq!(square.aspect_ratio(), 1.0);
+
+    Ok(())
+}
+
+fn test_error_display() -> Result<(), Error> {
+    fn open(path: &str) -> Result<Tga, Box<dyn std::error::Error>> {
+        Ok(Tga::new(path)?)
+    }
+    let err = open("example/images/missing.tga").unwrap_err();
+    assert!(err.to_string().starts_with("I/O error: "));
+    assert!(err.source().is_some());
+
+    let mismatch = Error::DimensionMismatch { expected: (4, 3), got: (2, 5) };
+    assert_eq!(mismatch.to_string(), "expected a 4x3 image, got 2x5");
+    assert!(std::error::Error::source(&mismatch).is_none());
+
+    Ok(())
+}
+
+#[cfg(feature = "rayon")]
+fn test_par_map_pixels() -> Result<(), Error> {
+    let mut parallel = Tga::new("example/images/UTC32.TGA")?;
+    let mut serial = Tga::new("example/images/UTC32.TGA")?;
+    let invert = |px: &mut [u8]| px[..3].iter_mut().for_each(|c| *c = 255 - *c);
+    parallel.par_map_pixels(invert)?;
+    serial.pixels_mut().chunks_exact_mut(4).for_each(invert);
+    assert_eq!(parallel, serial);
+
+    Ok(())
+}
+
+fn test_convert() -> Result<(), Error> {
+    let mut tga = Tga::new_rgb8(2, 1, vec![255, 0, 0, 10, 20, 30])?;
+    tga.convert(TgaPixelFormat::ARGB32)?;
+    assert_eq!(tga.info.pixel_format, TgaPixelFormat::ARGB32);
+    assert_eq!(tga.header.pixel_depth, 32);
+    assert!(tga.validate_against_header().is_ok());
+    // Stored as BGRA.
+    assert_eq!(tga.pixels(), [0, 0, 255, 255, 30, 20, 10, 255]);
+
+    tga.convert(TgaPixelFormat::BW8)?;
+    assert_eq!(tga.pixels(), [76, 18]);
+    tga.convert(TgaPixelFormat::RGB24)?;
+    assert_eq!(tga.pixels(), [76, 76, 76, 18, 18, 18]);
+
+    for format in [TgaPixelFormat::RGB555, TgaPixelFormat::BW16, TgaPixelFormat::RGB24] {
+        let mut utc16 = Tga::new("example/images/UTC16.TGA")?;
+        let expected = Tga::new("example/images/UTC16.TGA")?;
+        utc16.convert(format)?;
+        utc16.save("example/images/temp_convert.tga")?;
+        let reloaded = Tga::new("example/images/temp_convert.tga")?;
+        assert_eq!(reloaded.info.pixel_format, format);
+        if format != TgaPixelFormat::BW16 {
+            assert!(reloaded.rgb_equals(&expected)?);
+        }
+    }
+
+    let mut gray = Tga::new_gray8(1, 1, vec![200])?;
+    gray.convert(TgaPixelFormat::BW16)?;
+    assert_eq!(gray.pixels(), (200u16 * 257).to_le_bytes());
+
+    Ok(())
+}
+
+fn test_palette_image() -> Result<(), Error> {
+    let indexed = IndexedTga {
+        width: 1,
+        height: 1,
+        palette_format: TgaPixelFormat::RGB24,
+        palette: vec![0, 0, 255, 0, 255, 0, 255, 0, 0, 9, 9, 9],
+        indices: vec![0],
+    };
+    let swatches = indexed.palette_image(8, 2)?;
+    assert_eq!(swatches.dimensions(), (16, 16));
+    assert_eq!(swatches.sample(0.75, 0.75, WrapMode::Clamp), Some([9, 9, 9, 255]));
+    // Entry 1 is stored as B, G, R = 0, 255, 0.
+    assert_eq!(swatches.luminance_at(12, 4), Some(150));
+    assert_eq!(indexed.palette_image(8, 3)?.dimensions(), (24, 16));
+    assert!(indexed.palette_image(0, 2).is_err());
+
+    Ok(())
+}
+
+fn test_unpack_rgb555() -> Result<(), Error> {
+    let words = [0x7fffu16, 0x0421, 0xfc00, 0x001f];
+    let pixels: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+    let mut tga = Tga::from_raw(4, 1, TgaPixelFormat::RGB555, &pixels)?;
+    tga.unpack_rgb555_to_rgb24()?;
+    assert_eq!(tga.info.pixel_format, TgaPixelFormat::RGB24);
+    // Stored as BGR, the attribute bit of 0xfc00 is dropped.
+    assert_eq!(tga.pixels(), [255, 255, 255, 8, 8, 8, 0, 0, 255, 255, 0, 0]);
+    assert!(tga.unpack_rgb555_to_rgb24().is_err());
+
+    let mut utc16 = Tga::new("example/images/UTC16.TGA")?;
+    let expected = Tga::new("example/images/UTC16.TGA")?;
+    utc16.unpack_rgb555_to_rgb24()?;
+    assert!(utc16.rgb_equals(&expected)?);
+
+    Ok(())
+}
+
+fn test_resize_canvas() -> Result<(), Error> {
+    let tga = Tga::new_gray8(2, 2, vec![1, 2, 3, 4])?;
+    let padded = tga.resize_canvas(4, 4, Anchor::TopLeft, &[9])?;
+    assert_eq!(padded.pixels(), [
+        1, 2, 9, 9,
+        3, 4, 9, 9,
+        9, 9, 9, 9,
+        9, 9, 9, 9,
+    ]);
+
+    let centered = tga.resize_canvas(4, 3, Anchor::Center, &[0])?;
+    assert_eq!(centered.pixels(), [0, 1, 2, 0, 0, 3, 4, 0, 0, 0, 0, 0]);
+    let cropped = tga.resize_canvas(1, 1, Anchor::BottomRight, &[0])?;
+    assert_eq!(cropped.pixels(), [4]);
+    assert!(tga.resize_canvas(4, 4, Anchor::TopLeft, &[0, 0]).is_err());
+
+    Ok(())
+}
+
+fn test_image_id_skipped() -> Result<(), Error> {
+    // A non-empty image ID sits between the header and the color map.
+    write_unused_map_tga("example/images/temp_id_map.tga");
+    let mut bytes = fs::read("example/images/temp_id_map.tga")?;
+    bytes[0] = 5;
+    bytes.splice(18..18, *b"hello");
+    fs::write("example/images/temp_id_map.tga", &bytes)?;
+
+    let tga = Tga::new("example/images/temp_id_map.tga")?;
+    assert_eq!(tga.image_id, b"hello");
+    assert_eq!(tga.data_offset(), 18 + 5 + 9);
+    assert_eq!(tga.pixels(), [1, 2, 3, 4, 5, 6]);
+    assert_eq!(Tga::from_bytes(&bytes)?, tga);
+
+    Ok(())
+}
+
+fn test_bits_per_pixel() -> Result<(), Error> {
+    let utc16 = Tga::new("example/images/UTC16.TGA")?;
+    assert_eq!(utc16.bits_per_pixel(), 15);
+    assert_eq!(utc16.header.get_pixel_size()?, 2);
+    assert_eq!(utc16.to_vec()?[16], 16);
+
+    for (name, bits) in [("UBW8", 8), ("UTC24", 24), ("UTC32", 32)] {
+        let tga = Tga::new(&format!("example/images/{}.TGA", name))?;
+        assert_eq!(tga.bits_per_pixel(), bits);
+        assert_eq!(tga.header.get_pixel_size()? * 8, bits);
+    }
+
+    Ok(())
+}
+
+fn test_crop() -> Result<(), Error> {
+    // A 4x2 sheet of two 2x2 sprites.
+    let mut sheet = Tga::new_rgb8(4, 2, (0..24).collect())?;
+    sheet.crop(2, 0, 2, 2)?;
+    assert_eq!(sheet.dimensions(), (2, 2));
+    assert!(sheet.validate_against_header().is_ok());
+    assert_eq!(sheet.pixels(), [8, 7, 6, 11, 10, 9, 20, 19, 18, 23, 22, 21]);
+
+    assert!(matches!(sheet.crop(1, 1, 2, 1), Err(Error::InvalidImageDimensions)));
+    assert!(matches!(sheet.crop(0, 0, 0, 1), Err(Error::InvalidImageDimensions)));
+    assert_eq!(sheet.dimensions(), (2, 2));
+
+    Ok(())
+}
+
+fn test_rotate() -> Result<(), Error> {
+    // 2x3:
+    // 1 2
+    // 3 4
+    // 5 6
+    let tga = Tga::new_rgba8(2, 3, (1..=6).flat_map(|v| [v, 0, 0, 255]).collect())?;
+    let red = |tga: &Tga| tga.pixels().chunks_exact(4).map(|px| px[2]).collect::<Vec<u8>>();
+
+    let mut rotated = Tga::new_rgba8(2, 3, (1..=6).flat_map(|v| [v, 0, 0, 255]).collect())?;
+    rotated.rotate90()?;
+    assert_eq!(rotated.dimensions(), (3, 2));
+    assert_eq!(red(&rotated), [5, 3, 1, 6, 4, 2]);
+    assert_eq!(rotated.luminance_at(0, 0), tga.luminance_at(0, 2));
+
+    rotated.rotate90()?;
+    let mut half = Tga::new_rgba8(2, 3, (1..=6).flat_map(|v| [v, 0, 0, 255]).collect())?;
+    half.rotate180()?;
+    assert_eq!(red(&half), [6, 5, 4, 3, 2, 1]);
+    assert_eq!(half, rotated);
+    assert_eq!(half.pixels()[3], 255);
+
+    rotated.rotate90()?;
+    let mut counter = Tga::new_rgba8(2, 3, (1..=6).flat_map(|v| [v, 0, 0, 255]).collect())?;
+    counter.rotate270()?;
+    assert_eq!(counter.dimensions(), (3, 2));
+    assert_eq!(red(&counter), [2, 4, 6, 1, 3, 5]);
+    assert_eq!(counter, rotated);
+
+    Ok(())
+}
+
+fn test_max_pixels() -> Result<(), Error> {
+    // Only the header, the pixels would take 16 GiB.
+    let mut bytes = vec![0u8; 18];
+    bytes[2] = 2;
+    bytes[12..16].copy_from_slice(&[0xff; 4]);
+    bytes[16] = 32;
+    assert!(matches!(Tga::from_bytes(&bytes), Err(Error::ImageTooLarge)));
+
+    let tga = Tga::new("example/images/UTC24.TGA")?;
+    let small = DecodeOptions { max_pixels: Some(128 * 127), ..DecodeOptions::default() };
+    assert!(matches!(Tga::new_with_options("example/images/UTC24.TGA", &small), Err(Error::ImageTooLarge)));
+    let exact = DecodeOptions { max_pixels: Some(128 * 128), ..DecodeOptions::default() };
+    assert_eq!(Tga::new_with_options("example/images/UTC24.TGA", &exact)?, tga);
+    let unlimited = DecodeOptions { max_pixels: None, ..DecodeOptions::default() };
+    assert_eq!(Tga::new_with_options("example/images/UTC24.TGA", &unlimited)?, tga);
+
+    Ok(())
+}
+
+fn test_copy_to_strided() -> Result<(), Error> {
+    let tga = Tga::new_rgb8(2, 3, (1..=18).collect())?;
+    // 6 bytes per row padded to 8, the last row needs no padding.
+    let mut dst = vec![0xaa; 8 * 2 + 6];
+    tga.copy_to_strided(&mut dst, 8)?;
+    assert_eq!(dst, [
+        3, 2, 1, 6, 5, 4, 0xaa, 0xaa,
+        9, 8, 7, 12, 11, 10, 0xaa, 0xaa,
+        15, 14, 13, 18, 17, 16,
+    ]);
+
+    assert!(matches!(tga.copy_to_strided(&mut dst, 5), Err(Error::InvalidImageDimensions)));
+    assert!(matches!(tga.copy_to_strided(&mut dst[..21], 8), Err(Error::InvalidImageDimensions)));
+    let mut tight = vec![0; 18];
+    tga.copy_to_strided(&mut tight, 6)?;
+    assert_eq!(tight, tga.pixels());
+
+    Ok(())
+}
+
+fn test_resize_nearest() -> Result<(), Error> {
+    let mut tga = Tga::new_gray8(2, 2, vec![1, 2, 3, 4])?;
+    tga.resize_nearest(4, 4)?;
+    assert_eq!(tga.pixels(), [
+        1, 1, 2, 2,
+        1, 1, 2, 2,
+        3, 3, 4, 4,
+        3, 3, 4, 4,
+    ]);
+    tga.resize_nearest(3, 1)?;
+    assert_eq!(tga.pixels(), [1, 1, 2]);
+
+    tga.save("example/images/temp_nearest.tga")?;
+    assert_eq!(Tga::new("example/images/temp_nearest.tga")?.dimensions(), (3, 1));
+    assert!(matches!(tga.resize_nearest(0, 4), Err(Error::InvalidImageDimensions)));
+
+    Ok(())
+}
+
+fn test_resize_bilinear() -> Result<(), Error> {
+    let mut tga = Tga::new_gray8(2, 2, vec![0, 100, 100, 200])?;
+    tga.resize_bilinear(4, 4)?;
+    assert_eq!(tga.dimensions(), (4, 4));
+    // Pixel centers map to source coordinates 0.25 and 0.75 inside the image.
+    assert_eq!(tga.luminance_at(1, 1), Some(50));
+    assert_eq!(tga.luminance_at(2, 1), Some(100));
+    assert_eq!(tga.luminance_at(2, 2), Some(150));
+    assert_eq!(tga.luminance_at(0, 0), Some(0));
+    assert_eq!(tga.luminance_at(3, 3), Some(200));
+
+    let mut rgba = Tga::new_rgba8(2, 1, vec![0, 0, 0, 0, 200, 100, 50, 255])?;
+    rgba.resize_bilinear(4, 1)?;
+    assert_eq!(rgba.pixels()[4..8], [13, 25, 50, 64]);
+
+    let words: Vec<u8> = [0x0000u16, 0x7fff].iter().flat_map(|w| w.to_le_bytes()).collect();
+    let mut rgb555 = Tga::from_raw(2, 1, TgaPixelFormat::RGB555, &words)?;
+    rgb555.resize_bilinear(4, 1)?;
+    assert_eq!(rgb555.pixels(), [0, 0, 0, 0, 0xff, 0x7f, 0xff, 0x7f]);
+
+    Ok(())
+}
+
+fn test_footer() -> Result<(), Error> {
+    let tga = Tga::new("example/images/UTC24.TGA")?;
+    tga.save("example/images/temp_footer.tga")?;
+    let bytes = fs::read("example/images/temp_footer.tga")?;
+    assert_eq!(&bytes[bytes.len() - 18..], b"TRUEVISION-XFILE.\0");
+    assert_eq!(bytes[bytes.len() - 26..bytes.len() - 18], [0; 8]);
+    assert!(is_tga_v2(&mut std::io::Cursor::new(&bytes))?);
+    assert_eq!(Tga::new("example/images/temp_footer.tga")?, tga);
+
+    let mut original = Vec::new();
+    tga.write_to_with_options(&mut original, &SaveOptions { footer: false })?;
+    assert_eq!(original.len(), bytes.len() - 26);
+    assert_eq!(original, bytes[..original.len()]);
+
+    Ok(())
+}
+
+fn test_extension() -> Result<(), Error> {
+    let tga = Tga::new("example/images/UTC24.TGA")?;
+    let extension = tga.extension.as_ref().expect("UTC24 has an extension area");
+    assert_eq!(extension.author_name, "Ricky True");
+    assert!(extension.author_comments[0].starts_with("Sample 24 bit"));
+    assert_eq!(extension.timestamp, [2, 24, 1990, 10, 0, 0]);
+    assert_eq!(extension.software_id, "TGAEdit");
+    assert_eq!(extension.software_version, (140, b' '));
+
+    let mut file = fs::File::open("example/images/UTC24.TGA")?;
+    assert_eq!(Tga::read_extension(&mut file)?.as_ref(), Some(extension));
+    assert_eq!(file.stream_position()?, 0);
+
+    // The footer written by `save` has no extension area.
+    tga.save("example/images/temp_extension.tga")?;
+    assert_eq!(Tga::new("example/images/temp_extension.tga")?.extension, None);
+    assert_eq!(Tga::new_rgb8(1, 1, vec![0; 3])?.extension, None);
+
+    Ok(())
+}
+
+fn test_attribute_bits() -> Result<(), Error> {
+    let utc32 = Tga::new("example/images/UTC32.TGA")?;
+    assert_eq!(utc32.info.attribute_bits, 8);
+    assert!(utc32.info.has_alpha());
+    assert!(!Tga::new("example/images/UTC24.TGA")?.info.has_alpha());
+
+    // A 32 bits file declaring no attribute bits: the fourth byte isn't alpha.
+    write_raw_tga("example/images/temp_no_alpha.tga", 2, 2, 1, 32, &[1, 2, 3, 0, 4, 5, 6, 7]);
+    let mut tga = Tga::new("example/images/temp_no_alpha.tga")?;
+    assert_eq!(tga.info.attribute_bits, 0);
+    assert!(!tga.info.has_alpha());
+    tga.convert(TgaPixelFormat::RGB555)?;
+    assert_eq!(tga.pixels()[1] & 0x80, 0x80);
+    assert!(tga.info.has_alpha());
+
+    let mut tga = Tga::new("example/images/temp_no_alpha.tga")?;
+    tga.convert(TgaPixelFormat::BW8)?;
+    tga.convert(TgaPixelFormat::ARGB32)?;
+    assert_eq!(tga.pixels()[3], 255);
+    assert_eq!(saved_pixels(&Tga::new("example/images/temp_no_alpha.tga")?, "example/images/temp_no_alpha2.tga")?.len(), 8);
+    assert!(!Tga::new("example/images/temp_no_alpha2.tga")?.info.has_alpha());
+
+    Ok(())
+}
+
+fn test_save_colormapped() -> Result<(), Error> {
+    // Stored as BGR; the last entry repeats the first one and is never used.
+    let palette = [0, 0, 255, 0, 255, 0, 255, 0, 0, 0, 0, 255];
+    let pixels = [255, 0, 0, 0, 0, 255, 0, 0, 255, 0, 255, 0];
+    let mut tga = Tga::from_raw(2, 2, TgaPixelFormat::RGB24, &pixels)?;
+    assert!(matches!(tga.save_colormapped("example/images/temp_colormapped.tga"), Err(Error::NoData)));
+
+    tga.map = Some(ColorMap::new(5, 3, &palette)?);
+    tga.save_colormapped("example/images/temp_colormapped.tga")?;
+    let bytes = fs::read("example/images/temp_colormapped.tga")?;
+    assert_eq!(bytes[1..8], [1, 1, 5, 0, 4, 0, 24]);
+    assert_eq!(bytes[16], 8);
+    assert_eq!(bytes[18..30], palette);
+    assert_eq!(bytes[30..34], [7, 5, 5, 6]);
+    assert_eq!(bytes.len(), 34 + 26);
+    tga.save_colormapped_with_options("example/images/temp_colormapped_v1.tga", &SaveOptions { footer: false })?;
+    assert_eq!(fs::read("example/images/temp_colormapped_v1.tga")?, bytes[..34]);
+
+    let indexed = IndexedTga::load("example/images/temp_colormapped.tga")?;
+    assert_eq!(indexed.indices, [2, 0, 0, 1]);
+    assert!(indexed.expand()?.rgb_equals(&tga)?);
+
+    tga.pixels_mut()[0..3].copy_from_slice(&[1, 2, 3]);
+    assert!(matches!(tga.save_colormapped("example/images/temp_colormapped.tga"), Err(Error::ColorMapIndexFailed)));
+    tga.map = Some(ColorMap::new(255, 3, &palette)?);
+    tga.pixels_mut()[0..3].copy_from_slice(&[0, 0, 255]);
+    assert!(matches!(tga.save_colormapped("example/images/temp_colormapped.tga"), Err(Error::ColorMapIndexFailed)));
+    tga.convert(TgaPixelFormat::ARGB32)?;
+    assert!(tga.map.is_none());
+
+    assert!(matches!(ColorMap::new(0, 3, &palette[1..]), Err(Error::InvalidPaletteSize)));
+    assert!(matches!(ColorMap::new(0, 1, &palette), Err(Error::UnsupportedPixelFormat)));
+
+    Ok(())
+}
+
+fn test_to_colormapped() -> Result<(), Error> {
+    let mut tga = Tga::new("example/images/UTC24.TGA")?;
+    let original = Tga::new("example/images/UTC24.TGA")?;
+    let quantized = original.quantize(64)?;
+    tga.to_colormapped(64)?;
+    let map = tga.map.as_ref().unwrap();
+    assert!(map.entry_count <= 64);
+    assert_eq!(map.bytes_per_entry, 3);
+    assert!(tga.indexed);
+    assert_eq!(tga.pixels(), quantized.indices);
+    assert_eq!(map.entries(), quantized.palette);
+    assert_eq!((tga.header.image_type, tga.header.pixel_depth, tga.header.map_type), (1, 8, 1));
+    assert_eq!((tga.header.map_first_entry, tga.header.map_length, tga.header.map_entry_size), (0, map.entry_count, 24));
+    assert!(tga.validate_against_header().is_ok());
+    assert_eq!(tga.pixels_iter().len(), 128 * 128);
+
+    // The indices aren't pixel values.
+    assert!(matches!(tga.rgb_equals(&original), Err(Error::UnsupportedPixelFormat)));
+    assert!(matches!(tga.image_flip_h(), Err(Error::UnsupportedPixelFormat)));
+    assert_eq!(tga.get_pixel_rgba(0, 0), None);
+    assert!(!tga.set_pixel_rgba(0, 0, [0; 4]));
+    assert!(matches!(tga.to_colormapped(16), Err(Error::UnsupportedPixelFormat)));
+
+    tga.save("example/images/temp_to_colormapped.tga")?;
+    let bytes = fs::read("example/images/temp_to_colormapped.tga")?;
+    assert_eq!(bytes[1..3], [1, 1]);
+    assert_eq!(bytes[16], 8);
+    let entries = quantized.palette_len();
+    assert_eq!(bytes.len(), 18 + tga.image_id.len() + entries * 3 + 128 * 128 + 26);
+    let loaded = Tga::new("example/images/temp_to_colormapped.tga")?;
+    assert_eq!(loaded.header.image_type, 1);
+    assert!(loaded.rgb_equals(&quantized.expand()?)?);
+    assert_eq!(IndexedTga::load("example/images/temp_to_colormapped.tga")?, quantized);
+
+    tga.save_colormapped("example/images/temp_to_colormapped2.tga")?;
+    assert_eq!(fs::read("example/images/temp_to_colormapped2.tga")?, bytes);
+    tga.save_colormapped_with_options("example/images/temp_to_colormapped2.tga", &SaveOptions { footer: false })?;
+    assert_eq!(fs::read("example/images/temp_to_colormapped2.tga")?, bytes[..bytes.len() - 26]);
+
+    // Run-length encoded indices, as image type 9.
+    for mode in [RleMode::PerScanline, RleMode::Continuous] {
+        tga.save_rle("example/images/temp_to_colormapped_rle.tga", mode)?;
+        let rle = fs::read("example/images/temp_to_colormapped_rle.tga")?;
+        assert_eq!(rle[1..3], [1, 9]);
+        assert_eq!(rle[16], 8);
+        assert_eq!(rle[18 + tga.image_id.len()..][..entries * 3], quantized.palette);
+        let options = DecodeOptions { rle_mode: mode, ..DecodeOptions::default() };
+        let loaded = Tga::new_with_options("example/images/temp_to_colormapped_rle.tga", &options)?;
+        assert_eq!(loaded.header.image_type, 9);
+        assert!(loaded.rgb_equals(&quantized.expand()?)?);
+    }
+
+    let mut argb = Tga::new("example/images/UTC32.TGA")?;
+    argb.to_colormapped(256)?;
+    argb.save("example/images/temp_to_colormapped32.tga")?;
+    let loaded = Tga::new("example/images/temp_to_colormapped32.tga")?;
+    assert_eq!((loaded.header.image_type, loaded.header.map_entry_size), (1, 32));
+    assert!(loaded.approx_eq(&Tga::new("example/images/UTC32.TGA")?.quantize(256)?.expand()?, 0));
+
+    let mut other = Tga::new("example/images/UTC24.TGA")?;
+    assert!(matches!(other.to_colormapped(257), Err(Error::InvalidPaletteSize)));
+    let mut gray = Tga::new_gray8(2, 2, vec![0, 1, 2, 3])?;
+    assert!(matches!(gray.to_colormapped(4), Err(Error::UnsupportedPixelFormat)));
+
+    Ok(())
+}
+
+fn test_try_get_color() -> Result<(), Error> {
+    let map = ColorMap::new(0, 3, &[10, 20, 30, 40, 50, 60, 70, 80, 90])?;
+    let mut buf = [0u8; 3];
+    map.try_get_color(&mut buf, 1)?;
+    assert_eq!(buf, [40, 50, 60]);
+    map.try_get_color(&mut buf, 2)?;
+    assert_eq!(buf, [70, 80, 90]);
+    assert!(matches!(map.try_get_color(&mut buf, 3), Err(Error::ColorMapIndexFailed)));
+    assert!(matches!(map.try_get_color(&mut buf[..2], 0), Err(Error::ColorMapIndexFailed)));
+
+    // Indices start at `first_index`.
+    let map = ColorMap::new(4, 3, &[10, 20, 30, 40, 50, 60, 70, 80, 90])?;
+    map.try_get_color(&mut buf, 5)?;
+    assert_eq!(buf, [40, 50, 60]);
+    assert!(matches!(map.try_get_color(&mut buf, 3), Err(Error::ColorMapIndexFailed)));
+
+    Ok(())
+}
+
+// Writes a 3x1 color mapped image (type 1 or 9) with a 24 bits map of 3 entries
+// starting at 2, followed by `data`.
+fn write_colormapped_tga(path: &str, image_type: u8, data: &[u8]) {
+    let mut bytes = vec![0u8; 18];
+    bytes[1] = 1;
+    bytes[2] = image_type;
+    bytes[3] = 2;
+    bytes[5] = 3;
+    bytes[7] = 24;
+    bytes[12] = 3;
+    bytes[14] = 1;
+    bytes[16] = 8;
+    bytes[17] = 0x20;
+    bytes.extend_from_slice(&[10, 20, 30, 40, 50, 60, 70, 80, 90]);
+    bytes.extend_from_slice(data);
+    fs::write(path, bytes).unwrap();
+}
+
+fn test_colormapped_decode() -> Result<(), Error> {
+    write_colormapped_tga("example/images/temp_cm.tga", 1, &[3, 2, 4]);
+    let tga = Tga::new("example/images/temp_cm.tga")?;
+    assert_eq!(tga.info.pixel_format, TgaPixelFormat::RGB24);
+    assert_eq!(tga.pixels(), [40, 50, 60, 10, 20, 30, 70, 80, 90]);
+    assert!(tga.map.as_ref().unwrap().entries().is_empty());
+
+    // A run of two index 4 pixels, then a raw packet of one index 2 pixel.
+    write_colormapped_tga("example/images/temp_cm.tga", 9, &[0x81, 4, 0x00, 2]);
+    let tga = Tga::new("example/images/temp_cm.tga")?;
+    assert_eq!(tga.pixels(), [70, 80, 90, 70, 80, 90, 10, 20, 30]);
+
+    write_colormapped_tga("example/images/temp_cm.tga", 1, &[3, 5, 4]);
+    assert!(matches!(Tga::new("example/images/temp_cm.tga"), Err(Error::ColorMapIndexFailed)));
+
+    // The Truevision samples hold the same picture as UTC16.TGA.
+    let utc16 = Tga::new("example/images/UTC16.TGA")?;
+    let ucm8 = Tga::new("example/images/UCM8.TGA")?;
+    let ccm8 = Tga::new("example/images/CCM8.TGA")?;
+    assert_eq!(ucm8.info.pixel_format, TgaPixelFormat::RGB555);
+    assert_eq!(ucm8.pixels(), utc16.pixels());
+    assert_eq!(ccm8.pixels(), utc16.pixels());
+    ucm8.save("example/images/temp_ucm8_tc.tga")?;
+    assert_eq!(Tga::new("example/images/temp_ucm8_tc.tga")?.pixels(), utc16.pixels());
+
+    // Written back with the original palette.
+    let keep = DecodeOptions { keep_color_map: true, ..DecodeOptions::default() };
+    let ucm8 = Tga::new_with_options("example/images/UCM8.TGA", &keep)?;
+    assert_eq!(ucm8.map.as_ref().unwrap().entries().len(), 256 * 2);
+    ucm8.save_colormapped("example/images/temp_ucm8.tga")?;
+    let reloaded = Tga::new("example/images/temp_ucm8.tga")?;
+    assert_eq!(reloaded.pixels(), utc16.pixels());
+    assert_eq!(fs::metadata("example/images/temp_ucm8.tga")?.len(), 18 + 26 + 512 + 128 * 128 + 26);
+
+    Ok(())
+}
+
+fn test_rle_malformed() -> Result<(), Error> {
+    let tga = Tga::new("example/images/UTC24.TGA")?;
+    tga.save_rle("example/images/temp_rle_fuzz.tga", RleMode::Continuous)?;
+    let bytes = fs::read("example/images/temp_rle_fuzz.tga")?;
+    let data_start = 18 + tga.image_id.len();
+    let data_end = bytes.len() - 26;
+
+    // Every truncation inside the packets is an error.
+    for len in (data_start..data_end).step_by(97) {
+        assert!(matches!(Tga::from_bytes(&bytes[..len]), Err(Error::IllegalHeader)));
+    }
+
+    // Random damage to the packets may decode or fail, but never writes out of bounds.
+    let mut seed = 0x2545f491u32;
+    for _ in 0..200 {
+        let mut damaged = bytes.clone();
+        for _ in 0..8 {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            let at = data_start + (seed >> 8) as usize % (data_end - data_start);
+            damaged[at] = (seed >> 24) as u8 | 0x80;
+        }
+        if let Ok(decoded) = Tga::from_bytes(&damaged) {
+            assert_eq!(decoded.pixels().len(), 128 * 128 * 3);
+        }
+    }
+
+    // Run and raw packets longer than the image.
+    write_raw_tga("example/images/temp_rle_long.tga", 10, 2, 1, 24, &[0xff, 1, 2, 3]);
+    assert!(matches!(Tga::new("example/images/temp_rle_long.tga"), Err(Error::IllegalHeader)));
+    write_raw_tga("example/images/temp_rle_long.tga", 10, 2, 1, 24, &[0x02, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+    assert!(matches!(Tga::new("example/images/temp_rle_long.tga"), Err(Error::IllegalHeader)));
+
+    Ok(())
+}
+
+fn test_empty_color_map() -> Result<(), Error> {
+    // A color mapped image declaring a map of 0 entries.
+    let mut bytes = vec![0u8; 18];
+    bytes[1] = 1;
+    bytes[2] = 1;
+    bytes[7] = 24;
+    bytes[12] = 2;
+    bytes[14] = 1;
+    bytes[16] = 8;
+    bytes[17] = 0x20;
+    bytes.extend_from_slice(&[0, 0]);
+    assert!(matches!(Tga::from_bytes(&bytes), Err(Error::ColorMapIndexFailed)));
+    let keep = DecodeOptions { keep_color_map: true, ..DecodeOptions::default() };
+    assert!(matches!(Tga::from_reader_with_options(&mut std::io::Cursor::new(&bytes), &keep), Err(Error::ColorMapIndexFailed)));
+
+    // The same for run-length encoded data.
+    bytes[2] = 9;
+    bytes.truncate(18);
+    bytes.extend_from_slice(&[0x81, 0]);
+    assert!(matches!(Tga::from_bytes(&bytes), Err(Error::ColorMapIndexFailed)));
+
+    Ok(())
+}
+
+fn test_truncated_data() -> Result<(), Error> {
+    let tga = Tga::new("example/images/UTC24.TGA")?;
+    tga.save_with_options("example/images/temp_truncated.tga", &SaveOptions { footer: false })?;
+    let bytes = fs::read("example/images/temp_truncated.tga")?;
+    assert!(Tga::from_bytes(&bytes).is_ok());
+    let err = Tga::from_bytes(&bytes[..bytes.len() - 10]).unwrap_err();
+    assert!(matches!(err, Error::UnexpectedEof));
+    assert_eq!(err.to_string(), "the file ends before the end of the pixel data");
+
+    write_colormapped_tga("example/images/temp_truncated_cm.tga", 1, &[2, 3]);
+    assert!(matches!(Tga::new("example/images/temp_truncated_cm.tga"), Err(Error::UnexpectedEof)));
+
+    Ok(())
+}
+
+fn test_pixels_iter() -> Result<(), Error> {
+    let tga = Tga::new("example/images/UBW8.TGA")?;
+    let mut histogram = [0usize; 256];
+    for px in tga.pixels_iter() {
+        histogram[px[0] as usize] += 1;
+    }
+    assert_eq!(histogram.iter().sum::<usize>(), 128 * 128);
+
+    let tga = Tga::from_raw(2, 3, TgaPixelFormat::RGB555, &(0..12).collect::<Vec<u8>>())?;
+    assert_eq!(tga.pixels_iter().len(), 6);
+    assert_eq!(tga.pixels_iter().nth(1), Some(&[2u8, 3][..]));
+    let rows: Vec<&[u8]> = tga.rows_iter().collect();
+    assert_eq!(rows, [&[0u8, 1, 2, 3][..], &[4, 5, 6, 7], &[8, 9, 10, 11]]);
+
+    Ok(())
+}
+
+fn test_map_pixels() -> Result<(), Error> {
+    let original = Tga::new("example/images/UTC24.TGA")?;
+    let mut tga = Tga::new("example/images/UTC24.TGA")?;
+    let mut visited = 0;
+    tga.map_pixels(|px| {
+        px.iter_mut().for_each(|c| *c = 255 - *c);
+        visited += 1;
+    })?;
+    assert_eq!(visited, 128 * 128);
+    assert!(tga.pixels().iter().zip(original.pixels()).all(|(c, o)| *c == 255 - *o));
+
+    // Swapping B and R twice is a no-op.
+    let swap = |px: &mut [u8]| px.swap(0, 2);
+    tga.map_pixels(swap)?;
+    tga.map_pixels(swap)?;
+    assert!(tga.pixels().iter().zip(original.pixels()).all(|(c, o)| *c == 255 - *o));
+
+    Ok(())
+}
+
+fn test_pixel_rgba() -> Result<(), Error> {
+    let mut tga = Tga::new_rgb8(3, 2, vec![0; 18])?;
+    assert!(tga.set_pixel_rgba(2, 1, [10, 20, 30, 40]));
+    assert_eq!(tga.get_pixel_rgba(2, 1), Some([10, 20, 30, 255]));
+    // Stored as BGR.
+    assert_eq!(tga.pixels()[15..18], [30, 20, 10]);
+    assert!(!tga.set_pixel_rgba(3, 0, [1, 1, 1, 1]));
+    assert!(!tga.set_pixel_rgba(0, 2, [1, 1, 1, 1]));
+    assert_eq!(tga.get_pixel_rgba(3, 0), None);
+    assert_eq!(tga.get_pixel_rgba(0, 2), None);
+    assert!(tga.pixels()[..15].iter().all(|c| *c == 0));
+
+    let mut argb = Tga::new_rgba8(1, 1, vec![0; 4])?;
+    assert!(argb.set_pixel_rgba(0, 0, [10, 20, 30, 40]));
+    assert_eq!(argb.get_pixel_rgba(0, 0), Some([10, 20, 30, 40]));
+    argb.info.attribute_bits = 0;
+    assert_eq!(argb.get_pixel_rgba(0, 0), Some([10, 20, 30, 255]));
+
+    let mut rgb555 = Tga::from_raw(1, 1, TgaPixelFormat::RGB555, &[0, 0])?;
+    assert!(rgb555.set_pixel_rgba(0, 0, [255, 0, 255, 255]));
+    assert_eq!(rgb555.pixels(), 0xfc1fu16.to_le_bytes());
+    assert_eq!(rgb555.get_pixel_rgba(0, 0), Some([255, 0, 255, 255]));
+
+    let mut gray = Tga::new_gray8(1, 1, vec![0])?;
+    assert!(gray.set_pixel_rgba(0, 0, [255, 255, 255, 0]));
+    assert_eq!(gray.get_pixel_rgba(0, 0), Some([255, 255, 255, 255]));
+
+    Ok(())
+}
+
+fn test_to_rgba() -> Result<(), Error> {
+    let mut tga = Tga::new_rgba8(2, 1, vec![10, 20, 30, 40, 50, 60, 70, 80])?;
+    // Stored as B, G, R, A.
+    assert_eq!(tga.pixels(), [30, 20, 10, 40, 70, 60, 50, 80]);
+    tga.to_rgba()?;
+    assert!(tga.rgba_order);
+    assert_eq!(tga.pixels(), [10, 20, 30, 40, 50, 60, 70, 80]);
+    tga.to_rgba()?;
+    assert_eq!(tga.pixels(), [10, 20, 30, 40, 50, 60, 70, 80]);
+    assert!(matches!(tga.save("example/images/temp_rgba_order.tga"), Err(Error::UnsupportedPixelFormat)));
+
+    // Helpers reading or writing pixels as R, G, B, A refuse swapped pixels.
+    let other = Tga::new_rgba8(2, 1, vec![10, 20, 30, 40, 50, 60, 70, 80])?;
+    assert_eq!(tga.get_pixel_rgba(0, 0), None);
+    assert!(!tga.set_pixel_rgba(0, 0, [1, 2, 3, 4]));
+    assert_eq!(tga.luminance_at(0, 0), None);
+    assert_eq!(tga.sample(0.5, 0.5, WrapMode::Clamp), None);
+    assert!(!tga.approx_eq(&other, 255));
+    assert!(matches!(tga.rgb_equals(&other), Err(Error::UnsupportedPixelFormat)));
+    assert!(matches!(other.rgb_equals(&tga), Err(Error::UnsupportedPixelFormat)));
+    assert!(matches!(tga.quantize(2), Err(Error::UnsupportedPixelFormat)));
+    assert!(matches!(tga.split_channels(), Err(Error::UnsupportedPixelFormat)));
+    assert!(matches!(tga.threshold(128), Err(Error::UnsupportedPixelFormat)));
+    assert!(matches!(tga.alpha_from_luminance(), Err(Error::UnsupportedPixelFormat)));
+    assert!(matches!(tga.compose_over(&other), Err(Error::UnsupportedPixelFormat)));
+    assert!(matches!(tga.convert(TgaPixelFormat::RGB24), Err(Error::UnsupportedPixelFormat)));
+    assert_eq!(tga.pixels(), [10, 20, 30, 40, 50, 60, 70, 80]);
+
+    tga.to_bgra()?;
+    assert_eq!(tga.get_pixel_rgba(0, 0), Some([10, 20, 30, 40]));
+    assert!(!tga.rgba_order);
+    assert_eq!(tga.pixels(), [30, 20, 10, 40, 70, 60, 50, 80]);
+    tga.save("example/images/temp_rgba_order.tga")?;
+
+    let mut rgb = Tga::new("example/images/UTC24.TGA")?;
+    let original = rgb.pixels().to_vec();
+    rgb.to_rgba()?;
+    assert_eq!(rgb.pixels()[..3], [original[2], original[1], original[0]]);
+    rgb.to_bgra()?;
+    assert_eq!(rgb.pixels(), original);
+
+    assert!(matches!(Tga::new_gray8(1, 1, vec![0])?.to_rgba(), Err(Error::UnsupportedPixelFormat)));
+
+    Ok(())
+}
+
+fn test_developer_fields() -> Result<(), Error> {
+    let tga = Tga::new("example/images/UTC24.TGA")?;
+    assert!(tga.developer_fields.is_empty());
+
+    // Two fields, e.g. a sprite pivot point and a name, then the directory and the footer.
+    let mut bytes = Vec::new();
+    tga.write_to_with_options(&mut bytes, &SaveOptions { footer: false })?;
+    let pivot_offset = bytes.len() as u32;
+    bytes.extend_from_slice(&[16, 0, 32, 0]);
+    let name_offset = bytes.len() as u32;
+    bytes.extend_from_slice(b"hero");
+    let directory_offset = bytes.len() as u32;
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    for (tag, offset, size) in [(1u16, pivot_offset, 4u32), (7, name_offset, 4)] {
+        bytes.extend_from_slice(&tag.to_le_bytes());
+        bytes.extend_from_slice(&offset.to_le_bytes());
+        bytes.extend_from_slice(&size.to_le_bytes());
+    }
+    bytes.extend_from_slice(&[0; 4]);
+    bytes.extend_from_slice(&directory_offset.to_le_bytes());
+    bytes.extend_from_slice(b"TRUEVISION-XFILE.\0");
+
+    let loaded = Tga::from_bytes(&bytes)?;
+    assert_eq!(loaded.developer_fields, [
+        DeveloperField { tag: 1, offset: pivot_offset, size: 4, data: vec![16, 0, 32, 0] },
+        DeveloperField { tag: 7, offset: name_offset, size: 4, data: b"hero".to_vec() },
+    ]);
+    let mut cursor = std::io::Cursor::new(&bytes);
+    assert_eq!(Tga::read_developer_fields(&mut cursor)?, loaded.developer_fields);
+    assert_eq!(cursor.position(), 0);
+
+    // A field past the end of the file doesn't prevent decoding, but isn't read.
+    let size_at = directory_offset as usize + 2 + 10 + 6;
+    bytes[size_at..size_at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(Tga::from_bytes(&bytes)?.developer_fields.is_empty());
+    assert!(matches!(Tga::read_developer_fields(&mut std::io::Cursor::new(&bytes)), Err(Error::IllegalHeader)));
+
+    tga.save_with_crc("example/images/temp_developer.tga")?;
+    let loaded = Tga::new("example/images/temp_developer.tga")?;
+    assert_eq!(loaded.developer_fields.len(), 1);
+    assert_eq!(loaded.developer_fields[0].tag, 0x4352);
+    assert_eq!(loaded.developer_fields[0].size, 4);
+
+    Ok(())
+}
+
+fn test_view() -> Result<(), Error> {
+    // Stored bottom to top, with an image ID.
+    let bytes = fs::read("example/images/UTC24.TGA")?;
+    let decoded = Tga::new("example/images/UTC24.TGA")?;
+    let view = TgaView::new(&bytes)?;
+    assert_eq!(view.dimensions(), (128, 128));
+    assert!(!view.is_top_down());
+    assert_eq!(view.image_id(), decoded.image_id);
+    // The pixels alias the input.
+    assert_eq!(view.pixels().as_ptr(), bytes[18 + 26..].as_ptr());
+    assert!(view.rows_iter().eq(decoded.rows_iter()));
+    assert_eq!(view.rows_iter().len(), 128);
+    assert_eq!(view.to_tga()?, decoded);
+
+    // `save` writes top to bottom.
+    let saved = decoded.to_vec()?;
+    let view = TgaView::new(&saved)?;
+    assert!(view.is_top_down());
+    assert_eq!(view.pixels(), decoded.pixels());
+
+    assert!(matches!(TgaView::new(&bytes[..18 + 26 + 128 * 128 * 3 - 1]), Err(Error::UnexpectedEof)));
+    let rle = fs::read("example/images/CTC24.TGA")?;
+    assert!(matches!(TgaView::new(&rle), Err(Error::UnsupportedImageType)));
+
+    // Views when possible, decodes otherwise.
+    let view = TgaRef::parse(&bytes)?;
+    assert!(matches!(view, TgaRef::View(_)));
+    let owned = TgaRef::parse(&rle)?;
+    assert!(matches!(owned, TgaRef::Owned(_)));
+    assert_eq!(owned.info(), view.info());
+    assert!(owned.rows_iter().eq(view.rows_iter()));
+
+    Ok(())
+}
+
 fn main() {
     println!("{:?}",test_cbw8());
     println!("{:?}",test_ctc24());
     println!("{:?}",test_utc24());
+    println!("{:?}",test_sobel_edges());
+    println!("{:?}",test_box_blur());
+    println!("{:?}",test_heightmap_to_normal());
+    println!("{:?}",test_split_merge_channels());
+    println!("{:?}",test_pack_channels());
+    println!("{:?}",test_rle_modes());
+    println!("{:?}",test_save_rle_per_scanline());
+    println!("{:?}",test_require_format());
+    println!("{:?}",test_as_rgba_chunks());
+    println!("{:?}",test_dhash());
+    println!("{:?}",test_skip_unused_color_map());
+    println!("{:?}",test_normalize_for_atlas());
+    println!("{:?}",test_from_rows());
+    println!("{:?}",test_image_id());
+    println!("{:?}",test_into_luma16());
+    println!("{:?}",test_typed_constructors());
+    println!("{:?}",test_map_alpha());
+    println!("{:?}",test_threshold());
+    println!("{:?}",test_signed_distance_field());
+    println!("{:?}",test_save_create_dirs());
+    println!("{:?}",test_rgb_equals());
+    println!("{:?}",test_row_checksums());
+    println!("{:?}",test_new_uninit());
+    println!("{:?}",test_is_tga_v2());
+    println!("{:?}",test_looks_like_tga());
+    println!("{:?}",test_data_offset());
+    println!("{:?}",test_sample());
+    println!("{:?}",test_tile());
+    println!("{:?}",test_send_sync());
+    println!("{:?}",test_vignette());
+    println!("{:?}",test_suppress_spill());
+    println!("{:?}",test_quantize());
+    println!("{:?}",test_convolve());
+    println!("{:?}",test_compact_palette());
+    println!("{:?}",test_origin_bits());
+    println!("{:?}",test_memory_usage());
+    println!("{:?}",test_rle_packet_count());
+    println!("{:?}",test_compose_over_premultiplied());
+    println!("{:?}",test_duplicate_row_count());
+    println!("{:?}",test_to_cursor());
+    println!("{:?}",test_reinterpret_format());
+    println!("{:?}",test_rotate90());
+    println!("{:?}",test_dimensions_survive_transforms());
+    println!("{:?}",test_crop_to_aspect());
+    println!("{:?}",test_cycle_palette());
+    println!("{:?}",test_count_matching());
+    println!("{:?}",test_flood_fill());
+    println!("{:?}",test_indexed_grayscale());
+    println!("{:?}",test_luminance_at());
+    println!("{:?}",test_from_bytes());
+    println!("{:?}",test_set_palette_entry());
+    println!("{:?}",test_from_reader());
+    println!("{:?}",test_thumbnail_fit());
+    println!("{:?}",test_write_to());
+    println!("{:?}",test_alpha_from_luminance());
+    println!("{:?}",test_pixels());
+    println!("{:?}",test_save_with_crc());
+    println!("{:?}",test_flip_v_wide());
+    println!("{:?}",test_texture_descriptor());
+    println!("{:?}",test_flip_non_square());
+    println!("{:?}",test_decode_out_of_memory());
+    println!("{:?}",test_dimension_mismatch());
+    println!("{:?}",test_into_vec());
+    println!("{:?}",test_save_chunked());
+    println!("{:?}",test_from_raw());
+    println!("{:?}",test_aspect_ratio());
+    println!("{:?}",test_error_display());
+    println!("{:?}",test_convert());
+    println!("{:?}",test_palette_image());
+    println!("{:?}",test_unpack_rgb555());
+    println!("{:?}",test_resize_canvas());
+    println!("{:?}",test_image_id_skipped());
+    println!("{:?}",test_bits_per_pixel());
+    println!("{:?}",test_crop());
+    println!("{:?}",test_rotate());
+    println!("{:?}",test_max_pixels());
+    println!("{:?}",test_copy_to_strided());
+    println!("{:?}",test_resize_nearest());
+    println!("{:?}",test_resize_bilinear());
+    println!("{:?}",test_footer());
+    println!("{:?}",test_extension());
+    println!("{:?}",test_attribute_bits());
+    println!("{:?}",test_save_colormapped());
+    println!("{:?}",test_to_colormapped());
+    println!("{:?}",test_try_get_color());
+    println!("{:?}",test_colormapped_decode());
+    println!("{:?}",test_rle_malformed());
+    println!("{:?}",test_empty_color_map());
+    println!("{:?}",test_truncated_data());
+    println!("{:?}",test_pixels_iter());
+    println!("{:?}",test_map_pixels());
+    println!("{:?}",test_pixel_rgba());
+    println!("{:?}",test_to_rgba());
+    println!("{:?}",test_developer_fields());
+    println!("{:?}",test_view());
+    #[cfg(feature = "rayon")]
+    println!("{:?}",test_par_map_pixels());
+    #[cfg(feature = "png")]
+    println!("{:?}",test_save_png());
+    #[cfg(feature = "png")]
+    println!("{:?}",test_from_png());
+    #[cfg(feature = "png")]
+    println!("{:?}",test_png_limits());
+    #[cfg(feature = "image")]
+    println!("{:?}",test_dynamic_image());
 }
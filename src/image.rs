@@ -0,0 +1,65 @@
+use ::image::{ DynamicImage, GrayImage, ImageBuffer, Luma, RgbImage, RgbaImage };
+
+use crate::t1gars::{ pixel_to_rgba, Error, Tga, TgaPixelFormat };
+
+// Hands the pixels to the `image` crate: BW8 becomes Luma8, BW16 Luma16, RGB24 Rgb8 and
+// ARGB32 Rgba8. RGB555 is expanded to Rgb8, or Rgba8 if it declares an attribute bit.
+// Returns Err(UnsupportedPixelFormat) for indexed images or pixels swapped by `to_rgba`.
+impl TryFrom<&Tga> for DynamicImage {
+    type Error = Error;
+
+    fn try_from(tga: &Tga) -> Result<Self, Error> {
+        tga.require_file_order()?;
+        let pixel_size = tga.pixel_size()? as usize;
+        let (width, height) = (tga.info.width as u32, tga.info.height as u32);
+        let format = tga.info.pixel_format;
+        let rgba = |channels: usize| -> Vec<u8> {
+            tga.pixels()
+                .chunks_exact(pixel_size)
+                .flat_map(|px| {
+                    let mut rgba = pixel_to_rgba(&format, px);
+                    if !tga.info.has_alpha() {
+                        rgba[3] = 255;
+                    }
+                    rgba.into_iter().take(channels)
+                })
+                .collect()
+        };
+
+        let image = match format {
+            TgaPixelFormat::BW8 => GrayImage::from_raw(width, height, tga.pixels().to_vec()).map(DynamicImage::ImageLuma8),
+            TgaPixelFormat::BW16 => {
+                let samples = tga.pixels().chunks_exact(2).map(|px| u16::from_le_bytes([px[0], px[1]])).collect();
+                ImageBuffer::<Luma<u16>, Vec<u16>>::from_raw(width, height, samples).map(DynamicImage::ImageLuma16)
+            },
+            TgaPixelFormat::ARGB32 => RgbaImage::from_raw(width, height, rgba(4)).map(DynamicImage::ImageRgba8),
+            TgaPixelFormat::RGB555 if tga.info.has_alpha() => RgbaImage::from_raw(width, height, rgba(4)).map(DynamicImage::ImageRgba8),
+            TgaPixelFormat::RGB555 | TgaPixelFormat::RGB24 => RgbImage::from_raw(width, height, rgba(3)).map(DynamicImage::ImageRgb8),
+        };
+        image.ok_or(Error::InvalidImageDimensions)
+    }
+}
+
+impl Tga {
+    // Takes the pixels of an `image` crate image: Luma8 becomes BW8, Luma16 BW16, Rgb8
+    // RGB24 and Rgba8 ARGB32. Other layouts are converted to Rgba8 if they have alpha,
+    // Rgb8 otherwise.
+    // Returns Err(InvalidImageDimensions) if a dimension is 0 or above 65535.
+    pub fn from_dynamic_image(image: &DynamicImage) -> Result<Tga, Error> {
+        let (width, height) = match (u16::try_from(image.width()), u16::try_from(image.height())) {
+            (Ok(width), Ok(height)) => (width, height),
+            _ => return Err(Error::InvalidImageDimensions),
+        };
+
+        match image {
+            DynamicImage::ImageLuma8(gray) => Tga::new_gray8(width, height, gray.as_raw().clone()),
+            DynamicImage::ImageLuma16(gray) => {
+                let samples: Vec<u8> = gray.as_raw().iter().flat_map(|v| v.to_le_bytes()).collect();
+                Tga::from_raw(width, height, TgaPixelFormat::BW16, &samples)
+            },
+            DynamicImage::ImageRgb8(rgb) => Tga::new_rgb8(width, height, rgb.as_raw().clone()),
+            _ if image.color().has_alpha() => Tga::new_rgba8(width, height, image.to_rgba8().into_raw()),
+            _ => Tga::new_rgb8(width, height, image.to_rgb8().into_raw()),
+        }
+    }
+}
@@ -1,4 +1,8 @@
 pub mod preclude;
 mod t1gars;
+#[cfg(feature = "png")]
+mod png;
+#[cfg(feature = "image")]
+mod image;
 
 pub use crate::t1gars::*;
@@ -0,0 +1,106 @@
+use std::{ fs::File, io::{ BufReader, BufWriter } };
+
+use ::png::{ BitDepth, ColorType, DecodingError, EncodingError, Limits, Transformations };
+
+use crate::t1gars::{ pixel_to_rgba, DecodeOptions, Error, Tga, TgaPixelFormat };
+
+impl Tga {
+    // Saves the image as PNG. Grayscale images are written as PNG grayscale (16 bits for
+    // BW16), ARGB32 as RGBA and the other formats as RGB.
+    pub fn save_png(&self, path: &str) -> Result<(), Error> {
+        self.require_file_order()?;
+        let (color_type, bit_depth, channels) = match self.info.pixel_format {
+            TgaPixelFormat::BW8 => (ColorType::Grayscale, BitDepth::Eight, 1),
+            TgaPixelFormat::BW16 => (ColorType::Grayscale, BitDepth::Sixteen, 2),
+            TgaPixelFormat::RGB555 | TgaPixelFormat::RGB24 => (ColorType::Rgb, BitDepth::Eight, 3),
+            TgaPixelFormat::ARGB32 => (ColorType::Rgba, BitDepth::Eight, 4),
+        };
+        let pixel_size = self.pixel_size()? as usize;
+
+        let mut samples = Vec::with_capacity(self.pixels().len() / pixel_size * channels);
+        for px in self.pixels().chunks_exact(pixel_size) {
+            match self.info.pixel_format {
+                // PNG samples are big-endian.
+                TgaPixelFormat::BW16 => samples.extend_from_slice(&[px[1], px[0]]),
+                _ => samples.extend_from_slice(&pixel_to_rgba(&self.info.pixel_format, px)[..channels]),
+            }
+        }
+
+        let (width, height) = self.dimensions();
+        let mut encoder = ::png::Encoder::new(BufWriter::new(File::create(path)?), width as u32, height as u32);
+        encoder.set_color(color_type);
+        encoder.set_depth(bit_depth);
+        let mut writer = encoder.write_header().map_err(encoding_error)?;
+        writer.write_image_data(&samples).map_err(encoding_error)?;
+        writer.finish().map_err(encoding_error)?;
+
+        Ok(())
+    }
+}
+
+// Maps the errors of the `png` encoder, keeping the I/O ones as they are.
+fn encoding_error(error: EncodingError) -> Error {
+    match error {
+        EncodingError::IoError(error) => error.into(),
+        _ => Error::UnsupportedPixelFormat,
+    }
+}
+
+impl Tga {
+    // Loads a PNG file. RGBA, gray with alpha and transparent palette images become
+    // ARGB32, RGB and palette images RGB24, gray images BW8 (or BW16 for 16 bits samples).
+    // Color samples of 16 bits are reduced to 8 bits.
+    pub fn from_png(path: &str) -> Result<Tga, Error> {
+        Self::from_png_with_options(path, &DecodeOptions::default())
+    }
+
+    // Only `max_pixels` applies to PNG files. It's checked against the IHDR dimensions
+    // before the image data is decompressed.
+    pub fn from_png_with_options(path: &str, options: &DecodeOptions) -> Result<Tga, Error> {
+        let mut decoder = ::png::Decoder::new(BufReader::new(File::open(path)?));
+        // Palette images and gray images below 8 bits are expanded to 8 bits samples.
+        decoder.set_transformations(Transformations::EXPAND);
+        // 8 bytes per pixel at most, for 16 bits RGBA.
+        decoder.set_limits(Limits { bytes: options.max_pixels.map_or(usize::MAX, |max| max.saturating_mul(8)) });
+        let mut reader = decoder.read_info().map_err(decoding_error)?;
+
+        let (width, height) = reader.info().size();
+        if width > u16::MAX as u32 || height > u16::MAX as u32 {
+            return Err(Error::InvalidImageDimensions);
+        }
+        if options.max_pixels.is_some_and(|max| width as usize * height as usize > max) {
+            return Err(Error::ImageTooLarge);
+        }
+
+        let mut buf = vec![0u8; reader.output_buffer_size().ok_or(Error::ImageTooLarge)?];
+        let frame = reader.next_frame(&mut buf).map_err(decoding_error)?;
+        buf.truncate(frame.buffer_size());
+
+        let samples: Vec<u8> = match (frame.color_type, frame.bit_depth) {
+            (ColorType::Grayscale, _) | (_, BitDepth::Eight) => buf,
+            // Keeps the most significant byte of the big-endian samples.
+            _ => buf.iter().step_by(2).copied().collect(),
+        };
+        let (format, pixels): (TgaPixelFormat, Vec<u8>) = match (frame.color_type, frame.bit_depth) {
+            // PNG samples are big-endian.
+            (ColorType::Grayscale, BitDepth::Sixteen) => (TgaPixelFormat::BW16, samples.chunks_exact(2).flat_map(|s| [s[1], s[0]]).collect()),
+            (ColorType::Grayscale, _) => (TgaPixelFormat::BW8, samples),
+            (ColorType::GrayscaleAlpha, _) => (TgaPixelFormat::ARGB32, samples.chunks_exact(2).flat_map(|s| [s[0], s[0], s[0], s[1]]).collect()),
+            (ColorType::Rgb, _) => (TgaPixelFormat::RGB24, samples.chunks_exact(3).flat_map(|s| [s[2], s[1], s[0]]).collect()),
+            (ColorType::Rgba, _) => (TgaPixelFormat::ARGB32, samples.chunks_exact(4).flat_map(|s| [s[2], s[1], s[0], s[3]]).collect()),
+            (ColorType::Indexed, _) => return Err(Error::UnsupportedPixelFormat),
+        };
+
+        Tga::from_raw(width as u16, height as u16, format, &pixels)
+    }
+}
+
+// Maps the errors of the `png` decoder: malformed files, including bad chunk CRCs, are
+// Err(IllegalHeader) and files above the limits Err(ImageTooLarge).
+fn decoding_error(error: DecodingError) -> Error {
+    match error {
+        DecodingError::IoError(error) => error.into(),
+        DecodingError::LimitsExceeded => Error::ImageTooLarge,
+        _ => Error::IllegalHeader,
+    }
+}
@@ -1,14 +1,27 @@
-use std::io::{ Seek, SeekFrom };
-use std::{ fs::File, io::Read, io::Write, path::Path };
+use std::collections::{ HashMap, VecDeque };
+use std::io::{ Cursor, Seek, SeekFrom };
+use std::{ fs, fs::File, io::Read, io::Write, path::Path };
 use std::mem;
 use std::alloc::{ Layout, self };
 use std::slice;
+use std::fmt;
 use std::ptr;
 
 const TGA_MAX_IMAGE_DIMENSIONS: u32 = 65535;
 const HEADER_SIZE: usize = 18;
-
-#[derive(PartialEq, Eq, Debug)]
+const FOOTER_SIZE: usize = 26;
+const FOOTER_SIGNATURE: &[u8; 18] = b"TRUEVISION-XFILE.\0";
+const EXTENSION_SIZE: usize = 495;
+// Default pixel count limit of decoding, 64M pixels (256 MiB as ARGB32).
+const DEFAULT_MAX_PIXELS: usize = 64 * 1024 * 1024;
+// Developer area tag of the CRC32 written by `save_with_crc`. Tags below 32768 are free
+// for developers, this one spells "CR".
+const CRC_DEVELOPER_TAG: u16 = 0x4352;
+
+// True color formats are named after their channels, but store them in the byte order
+// of the file: B, G, R for RGB24 and B, G, R, A for ARGB32 (a little-endian 0xAARRGGBB
+// word). `Tga::to_rgba` swaps them for consumers expecting R, G, B(, A).
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
 pub enum TgaPixelFormat {
     BW8,
     BW16,
@@ -30,20 +43,93 @@ pub enum Error {
     InvalidImageDimensions,
     ColorMapIndexFailed,
     IllegalHeader,
+    ImageIdTooLong,
+    InvalidPaletteSize,
+    InvalidKernel,
+    ChecksumMismatch,
+    // The dimensions of the second image of a binary operation differ from the first.
+    DimensionMismatch { expected: (u16, u16), got: (u16, u16) },
+    ImageTooLarge,
+    // The file ends before all the pixels declared by the header.
+    UnexpectedEof,
     IOError(std::io::Error),
 }
 
 #[derive(Debug)]
 pub struct LayPtr(Layout, *mut u8);
 
+impl LayPtr {
+    // Allocates `size` bytes, set to zero if `zeroed` is true.
+    // Returns Err(ErrorOutOfMemory) if the size can't be laid out or the allocator fails,
+    // rather than keeping a null pointer around.
+    // The allocator must not be called for 0 bytes (e.g. an empty color map), so these
+    // get a dangling, never dereferenced pointer instead.
+    fn alloc(size: usize, zeroed: bool) -> Result<Self, Error> {
+        let layout = Layout::from_size_align(size * mem::size_of::<u8>(), mem::size_of::<u8>())
+            .map_err(|_| Error::ErrorOutOfMemory)?;
+        if layout.size() == 0 {
+            return Ok(LayPtr(layout, ptr::NonNull::dangling().as_ptr()));
+        }
+        let ptr = unsafe {
+            match zeroed {
+                true => alloc::alloc_zeroed(layout),
+                false => alloc::alloc(layout),
+            }
+        };
+        if ptr.is_null() {
+            return Err(Error::ErrorOutOfMemory);
+        }
+        Ok(LayPtr(layout, ptr))
+    }
+}
+
+// The pointer uniquely owns its allocation: it's never aliased by another LayPtr and
+// it's only written through `&mut` access to the owner, so moving or sharing it between
+// threads is as safe as for a Box<[u8]>.
+unsafe impl Send for LayPtr {}
+unsafe impl Sync for LayPtr {}
+
 impl Drop for LayPtr {
    fn drop(&mut self) {
-       if !self.1.is_null() {
+       if !self.1.is_null() && self.0.size() != 0 {
            unsafe { alloc::dealloc(self.1, self.0) }
        }
    } 
 }
 
+// How run-length packets may be laid out relative to scanlines.
+// The original specification forbids packets crossing a scanline, TGA 2.0 producers
+// may emit continuous runs over the whole image.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum RleMode {
+    #[default]
+    PerScanline,
+    Continuous,
+}
+
+// Options controlling how an image is saved.
+#[derive(Debug, Clone)]
+pub struct SaveOptions {
+    // Appends the TGA 2.0 footer. Disable it to write a strict original format file.
+    pub footer: bool,
+}
+
+// Options controlling how an image is decoded.
+#[derive(Debug, Clone)]
+pub struct DecodeOptions {
+    // `PerScanline` (the default) rejects packets crossing scanlines as the original
+    // specification requires, `Continuous` accepts them.
+    pub rle_mode: RleMode,
+    // Compares the pixels with the CRC32 written by `save_with_crc`, if the file has one.
+    pub verify_crc: bool,
+    // Rejects images with more pixels before allocating them, to defend against
+    // decompression bombs. None means no limit.
+    pub max_pixels: Option<usize>,
+    // Keeps `Tga::map` populated after expanding a color mapped image, so that
+    // `save_colormapped` can write it back with its original palette.
+    pub keep_color_map: bool,
+}
+
 #[derive(PartialEq, Eq)]
 pub enum TgaImageType {
     NoData = 0 ,
@@ -55,7 +141,7 @@ pub enum TgaImageType {
     RLEGrayScale = 11,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct TgaHeader {
     pub id_length: u8,
     pub map_type: u8,
@@ -75,11 +161,13 @@ pub struct TgaHeader {
     pub image_descripter: u8,
 }
 
-#[derive(Debug)]
+#[derive(PartialEq, Eq, Debug)]
 pub struct TgaInfo {
     pub width: u16,
     pub height: u16,
     pub pixel_format: TgaPixelFormat,
+    // Number of attribute (alpha) bits per pixel, the low 4 bits of the image descriptor.
+    pub attribute_bits: u8,
 }
 
 #[derive(Debug)]
@@ -90,12 +178,165 @@ pub struct ColorMap {
     pub pixels: LayPtr,
 }
 
+// How texture coordinates outside [0, 1] are mapped back onto the image.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum WrapMode {
+    Clamp,
+    Repeat,
+    Mirror,
+}
+
+// How pixels are sampled when scaling an image.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum ScaleFilter {
+    // Copies the closest source pixel.
+    #[default]
+    Nearest,
+    // Interpolates the four closest source pixels. Falls back to `Nearest` for
+    // RGB555 and BW16 which don't have 8 bits channels.
+    Bilinear,
+}
+
+// Graphics API neutral texel formats, named after the byte order in memory.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum GpuFormat {
+    R8,
+    // 16 bits little-endian red channel.
+    R16,
+    // 16 bits little-endian A1R5G5B5 word, blue in the low bits.
+    Bgr5A1,
+    Bgr8,
+    Bgra8,
+}
+
+// What a graphics API needs to upload an image as a texture.
+#[derive(PartialEq, Eq, Debug)]
+pub struct TextureDescriptor<'a> {
+    pub width: u16,
+    pub height: u16,
+    pub format: GpuFormat,
+    // Bytes from the start of a row to the start of the next one.
+    pub row_pitch: usize,
+    // Rows from top to bottom.
+    pub data: &'a [u8],
+}
+
+// Where a smaller region is placed inside an image.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    #[default]
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+// A rectangle in pixel coordinates, origin at the upper left corner.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct Rect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
 #[derive(Debug)]
 pub struct Tga {
     pub header: TgaHeader,
     pub info: TgaInfo,
     pub data: LayPtr,
     pub map: Option<ColorMap>,
+    // Free-form image ID field, at most 255 bytes.
+    pub image_id: Vec<u8>,
+    // Whether the color channels are premultiplied by alpha.
+    pub premultiplied: bool,
+    // Whether `to_rgba` swapped the true color pixels to R, G, B(, A) byte order.
+    pub rgba_order: bool,
+    // Whether `data` holds an 8 bits index into `map` per pixel, as `to_colormapped`
+    // leaves it, rather than the pixels themselves.
+    pub indexed: bool,
+    // The TGA 2.0 extension area, if the file has one.
+    pub extension: Option<TgaExtension>,
+    // The tagged fields of the TGA 2.0 developer area, in directory order.
+    pub developer_fields: Vec<DeveloperField>,
+}
+
+// A field of the TGA 2.0 developer area. `offset` is from the start of the file.
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct DeveloperField {
+    pub tag: u16,
+    pub offset: u32,
+    pub size: u32,
+    pub data: Vec<u8>,
+}
+
+// The TGA 2.0 extension area. Text fields are cut at their first NUL byte.
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct TgaExtension {
+    pub author_name: String,
+    // Four lines of comments.
+    pub author_comments: [String; 4],
+    // Month, day, year, hour, minute, second. All 0 if not set.
+    pub timestamp: [u16; 6],
+    pub job_name: String,
+    // Hours, minutes, seconds.
+    pub job_time: [u16; 3],
+    pub software_id: String,
+    // Version number times 100 and a version letter (b' ' if none).
+    pub software_version: (u16, u8),
+    // Background color as A, R, G, B from the high to the low byte.
+    pub key_color: u32,
+    // Pixel width to height ratio as numerator and denominator, 0 denominator if unset.
+    pub pixel_aspect_ratio: (u16, u16),
+    // Gamma as numerator and denominator, 0 denominator if unset.
+    pub gamma: (u16, u16),
+    pub color_correction_offset: u32,
+    pub postage_stamp_offset: u32,
+    pub scan_line_offset: u32,
+    // What the alpha channel holds, e.g. 3 for useful alpha, 4 for premultiplied alpha.
+    pub attributes_type: u8,
+}
+
+// Two images are equal if they have the same dimensions, pixel format and pixels.
+impl PartialEq for Tga {
+    fn eq(&self, other: &Self) -> bool {
+        self.info == other.info && self.indexed == other.indexed && self.pixels() == other.pixels()
+    }
+}
+
+// A read-only image borrowing its pixels from the encoded bytes, without allocating.
+// Only uncompressed true color and grayscale files stored left to right can be viewed.
+#[derive(Debug)]
+pub struct TgaView<'a> {
+    pub header: TgaHeader,
+    pub info: TgaInfo,
+    image_id: &'a [u8],
+    pixels: &'a [u8],
+}
+
+// Either a view of the encoded bytes or, for files that need decoding, an owned image.
+#[derive(Debug)]
+pub enum TgaRef<'a> {
+    View(TgaView<'a>),
+    Owned(Box<Tga>),
+}
+
+// A color mapped image: one palette index per pixel and the palette entries.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct IndexedTga {
+    pub width: u16,
+    pub height: u16,
+    // Format of the palette entries.
+    pub palette_format: TgaPixelFormat,
+    // Palette entries, tightly packed in `palette_format`.
+    pub palette: Vec<u8>,
+    // One palette index per pixel in row-major order.
+    pub indices: Vec<u8>,
 }
 
 impl From<std::io::Error> for Error {
@@ -104,44 +345,81 @@ impl From<std::io::Error> for Error {
     }
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NoError => write!(f, "no error"),
+            Error::ErrorOutOfMemory => write!(f, "out of memory"),
+            Error::FileCannotRead => write!(f, "the file cannot be read"),
+            Error::FileCannotWrite => write!(f, "the file cannot be written"),
+            Error::NoData => write!(f, "the image has no data"),
+            Error::UnsupportedColorMapType => write!(f, "unsupported color map type"),
+            Error::UnsupportedImageType => write!(f, "unsupported image type"),
+            Error::UnsupportedPixelFormat => write!(f, "unsupported pixel format"),
+            Error::InvalidImageDimensions => write!(f, "invalid image dimensions"),
+            Error::ColorMapIndexFailed => write!(f, "color map index out of the color map"),
+            Error::IllegalHeader => write!(f, "illegal header or malformed data"),
+            Error::ImageIdTooLong => write!(f, "image ID longer than 255 bytes"),
+            Error::InvalidPaletteSize => write!(f, "invalid palette size"),
+            Error::InvalidKernel => write!(f, "invalid convolution kernel"),
+            Error::ChecksumMismatch => write!(f, "pixel data doesn't match its checksum"),
+            Error::DimensionMismatch { expected, got } => {
+                write!(f, "expected a {}x{} image, got {}x{}", expected.0, expected.1, got.0, got.1)
+            },
+            Error::ImageTooLarge => write!(f, "the image has more pixels than allowed"),
+            Error::UnexpectedEof => write!(f, "the file ends before the end of the pixel data"),
+            Error::IOError(err) => write!(f, "I/O error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::IOError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
 impl TgaHeader {
     pub fn new() -> Self {
         Self::default()
     }
 
     #[cfg(target_endian = "little")]
-    pub fn from_file(f: &mut File) -> Result<Self, Error> {
+    pub fn from_file<R: Read>(f: &mut R) -> Result<Self, Error> {
         let mut header = TgaHeader::new();
         let mut buf_1bytes: [u8; 1] = [0; 1];
         let mut buf_2bytes: [u8; 2] = [0; 2];
 
-        f.read(&mut buf_1bytes)?;
+        f.read_exact(&mut buf_1bytes)?;
         header.id_length = buf_1bytes[0];
-        f.read(&mut buf_1bytes)?;
+        f.read_exact(&mut buf_1bytes)?;
         header.map_type = buf_1bytes[0];
-        f.read(&mut buf_1bytes)?;
+        f.read_exact(&mut buf_1bytes)?;
         header.image_type = buf_1bytes[0];
 
-        f.read(&mut buf_2bytes)?;
+        f.read_exact(&mut buf_2bytes)?;
         header.map_first_entry = (buf_2bytes[0] as u16) + ((buf_2bytes[1] as u16) << 8);
-        f.read(&mut buf_2bytes)?;
+        f.read_exact(&mut buf_2bytes)?;
         header.map_length = (buf_2bytes[0] as u16) + ((buf_2bytes[1] as u16) << 8);
 
-        f.read(&mut buf_1bytes)?;
+        f.read_exact(&mut buf_1bytes)?;
         header.map_entry_size = buf_1bytes[0];
 
-        f.read(&mut buf_2bytes)?;
+        f.read_exact(&mut buf_2bytes)?;
         header.image_x_origin = (buf_2bytes[0] as u16) + ((buf_2bytes[1] as u16) << 8);
-        f.read(&mut buf_2bytes)?;
+        f.read_exact(&mut buf_2bytes)?;
         header.image_y_origin = (buf_2bytes[0] as u16) + ((buf_2bytes[1] as u16) << 8);
-        f.read(&mut buf_2bytes)?;
+        f.read_exact(&mut buf_2bytes)?;
         header.image_width = (buf_2bytes[0] as u16) + ((buf_2bytes[1] as u16) << 8);
-        f.read(&mut buf_2bytes)?;
+        f.read_exact(&mut buf_2bytes)?;
         header.image_height = (buf_2bytes[0] as u16) + ((buf_2bytes[1] as u16) << 8);
 
-        f.read(&mut buf_1bytes)?;
+        f.read_exact(&mut buf_1bytes)?;
         header.pixel_depth = buf_1bytes[0];
-        f.read(&mut buf_1bytes)?;
+        f.read_exact(&mut buf_1bytes)?;
         header.image_descripter = buf_1bytes[0];
 
         // Checks attributes of TgaHeader.
@@ -155,7 +433,7 @@ impl TgaHeader {
 
         header.is_supported_image_type()?;
 
-        if header.image_width <= 0 || header.image_height <= 0 {
+        if !check_dimensions(header.image_width.into(), header.image_height.into()) {
             return Err(Error::InvalidImageDimensions);
         }
 
@@ -165,38 +443,38 @@ impl TgaHeader {
     }
 
     #[cfg(target_endian = "big")]
-    pub fn from_file(f: &mut File) -> Result<Self, Error> {
+    pub fn from_file<R: Read>(f: &mut R) -> Result<Self, Error> {
         let mut header = TgaHeader::new();
         let mut buf_1bytes: [u8; 1] = [0; 1];
         let mut buf_2bytes: [u8; 2] = [0; 2];
-        f.read(&mut buf_1bytes)?;
+        f.read_exact(&mut buf_1bytes)?;
         header.id_length = buf_1bytes[0];
-        f.read(&mut buf_1bytes)?;
+        f.read_exact(&mut buf_1bytes)?;
         header.map_type = buf_1bytes[0];
-        f.read(&mut buf_1bytes)?;
+        f.read_exact(&mut buf_1bytes)?;
         header.image_type = buf_1bytes[0];
 
-        f.read(&mut buf_2bytes)?;
+        f.read_exact(&mut buf_2bytes)?;
         header.map_first_entry = ((buf_2bytes[1] as u16) + ((buf_2bytes[0] as u16) << 8));
-        f.read(&mut buf_2bytes)?;
+        f.read_exact(&mut buf_2bytes)?;
         header.map_length = (buf_2bytes[1] as u16) + ((buf_2bytes[0] as u16) << 8);
 
-        f.read(&mut buf_1bytes)?;
+        f.read_exact(&mut buf_1bytes)?;
         header.map_entry_size = buf_1bytes[0];
 
-        f.read(&mut buf_2bytes)?;
+        f.read_exact(&mut buf_2bytes)?;
         header.image_x_origin = (buf_2bytes[1] as u16) + ((buf_2bytes[0] as u16) << 8);
-        f.read(&mut buf_2bytes)?;
+        f.read_exact(&mut buf_2bytes)?;
         header.image_y_origin = (buf_2bytes[1] as u16) + ((buf_2bytes[0] as u16) << 8);
-        f.read(&mut buf_2bytes)?;
+        f.read_exact(&mut buf_2bytes)?;
         header.image_width = (buf_2bytes[1] as u16) + ((buf_2bytes[0] as u16) << 8);
-        f.read(&mut buf_2bytes)?;
+        f.read_exact(&mut buf_2bytes)?;
         header.image_height = (buf_2bytes[1] as u16) + ((buf_2bytes[0] as u16) << 8);
 
 
-        f.read(&mut buf_1bytes)?;
+        f.read_exact(&mut buf_1bytes)?;
         header.pixel_depth = buf_1bytes[0];
-        f.read(&mut buf_1bytes)?;
+        f.read_exact(&mut buf_1bytes)?;
         header.image_descripter = buf_1bytes[0];
 
         // Checks attributes of TgaHeader.
@@ -210,7 +488,7 @@ impl TgaHeader {
 
         header.is_supported_image_type()?;
 
-        if header.image_width <= 0 || header.image_height <= 0 {
+        if !check_dimensions(header.image_width.into(), header.image_height.into()) {
             return Err(Error::InvalidImageDimensions);
         }
 
@@ -233,6 +511,49 @@ impl TgaHeader {
         }
     }
 
+    // Parses the fields of a raw header without checking them.
+    fn from_bytes(bytes: &[u8; HEADER_SIZE]) -> Self {
+        let word = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+        Self {
+            id_length: bytes[0],
+            map_type: bytes[1],
+            image_type: bytes[2],
+            map_first_entry: word(3),
+            map_length: word(5),
+            map_entry_size: bytes[7],
+            image_x_origin: word(8),
+            image_y_origin: word(10),
+            image_width: word(12),
+            image_height: word(14),
+            pixel_depth: bytes[16],
+            image_descripter: bytes[17],
+        }
+    }
+
+    // Makes a header describing an uncompressed image in the given pixel format.
+    fn for_format(width: u16, height: u16, format: &TgaPixelFormat) -> Self {
+        let (image_type, pixel_depth) = match format {
+            TgaPixelFormat::BW8 => (TgaImageType::GrayScale, 8),
+            TgaPixelFormat::BW16 => (TgaImageType::GrayScale, 16),
+            TgaPixelFormat::RGB555 => (TgaImageType::TrueColor, 16),
+            TgaPixelFormat::RGB24 => (TgaImageType::TrueColor, 24),
+            TgaPixelFormat::ARGB32 => (TgaImageType::TrueColor, 32),
+        };
+
+        Self {
+            image_type: image_type as u8,
+            image_width: width,
+            image_height: height,
+            pixel_depth,
+            image_descripter: match format {
+                TgaPixelFormat::ARGB32 => 8,
+                TgaPixelFormat::RGB555 => 1,
+                _ => 0,
+            },
+            ..Self::default()
+        }
+    }
+
     // Gets the pixel format according to the header.
     // Returns Ok(_) means the header is not illegal, otherwise returns Err(_).
     #[inline]
@@ -288,25 +609,41 @@ impl TgaHeader {
     }
 }
 
-impl Default for TgaHeader {
+impl Default for SaveOptions {
+    fn default() -> Self {
+        Self { footer: true }
+    }
+}
+
+impl Default for DecodeOptions {
     fn default() -> Self {
         Self {
-            id_length: 0,
-            map_type: 0,
-            image_type: 0,
-            map_first_entry: 0,
-            map_length: 0,
-            map_entry_size: 0,
-            image_x_origin: 0,
-            image_y_origin: 0,
-            image_width: 0,
-            image_height: 0,
-            pixel_depth: 0,
-            image_descripter: 0
+            rle_mode: RleMode::PerScanline,
+            verify_crc: false,
+            max_pixels: Some(DEFAULT_MAX_PIXELS),
+            keep_color_map: false,
         }
     }
 }
 
+impl Anchor {
+    // Gets the upper left corner of a region leaving `free_x` by `free_y` pixels
+    // uncovered in the image. Negative values mean the region overflows the image.
+    fn offset(self, free_x: i32, free_y: i32) -> (i32, i32) {
+        let x = match self {
+            Anchor::TopLeft | Anchor::Left | Anchor::BottomLeft => 0,
+            Anchor::Top | Anchor::Center | Anchor::Bottom => free_x / 2,
+            Anchor::TopRight | Anchor::Right | Anchor::BottomRight => free_x,
+        };
+        let y = match self {
+            Anchor::TopLeft | Anchor::Top | Anchor::TopRight => 0,
+            Anchor::Left | Anchor::Center | Anchor::Right => free_y / 2,
+            Anchor::BottomLeft | Anchor::Bottom | Anchor::BottomRight => free_y,
+        };
+        (x, y)
+    }
+}
+
 impl TgaInfo {
     pub fn new() -> Self {
         Self::default()
@@ -319,8 +656,27 @@ impl TgaInfo {
             width: header.image_width,
             height: header.image_height,
             pixel_format: format,
+            attribute_bits: header.image_descripter & 0x0F,
         })
     }
+
+    // Makes the info of an image in the given pixel format, with the attribute bits
+    // the format usually declares (8 for ARGB32, 1 for RGB555).
+    pub fn for_format(width: u16, height: u16, format: TgaPixelFormat) -> Self {
+        Self {
+            width,
+            height,
+            pixel_format: format,
+            attribute_bits: TgaHeader::for_format(width, height, &format).image_descripter & 0x0F,
+        }
+    }
+
+    // Returns true if the pixels carry a meaningful alpha channel, i.e. the format has
+    // room for one and the image declares attribute bits.
+    pub fn has_alpha(&self) -> bool {
+        matches!(self.pixel_format, TgaPixelFormat::ARGB32 | TgaPixelFormat::RGB555)
+            && self.attribute_bits > 0
+    }
 }
 
 impl Default for TgaInfo {
@@ -328,55 +684,336 @@ impl Default for TgaInfo {
         TgaInfo {
             width: 0,
             height: 0,
-            pixel_format: TgaPixelFormat::ARGB32
+            pixel_format: TgaPixelFormat::ARGB32,
+            attribute_bits: 8,
         }
     }
 }
 
 impl ColorMap {
-    #[inline]
-    pub fn try_get_color(&self, buf: &mut [u8], mut index: u16) -> Result<(), Error> {
+    // Makes a color map from the stored entries, `bytes_per_entry` bytes each, the first
+    // one being `first_index`.
+    // Returns Err(InvalidPaletteSize) if `palette` isn't a whole number of entries or
+    // has more than 65535 of them, and Err(UnsupportedPixelFormat) for an entry size
+    // other than 2, 3 or 4 bytes.
+    pub fn new(first_index: u16, bytes_per_entry: u8, palette: &[u8]) -> Result<Self, Error> {
+        if !matches!(bytes_per_entry, 2..=4) {
+            return Err(Error::UnsupportedPixelFormat);
+        }
+        if palette.is_empty() || !palette.len().is_multiple_of(bytes_per_entry as usize) {
+            return Err(Error::InvalidPaletteSize);
+        }
+        let entry_count = u16::try_from(palette.len() / bytes_per_entry as usize).map_err(|_| Error::InvalidPaletteSize)?;
+
+        let pixels = LayPtr::alloc(palette.len(), false)?;
         unsafe {
-            index -= self.first_index;
-            if index >= self.entry_count {
-                return Err(Error::ColorMapIndexFailed);
-            }
-            ptr::copy_nonoverlapping(self.pixels.1, buf.as_mut_ptr().add(index as usize * self.bytes_per_entry as usize), self.bytes_per_entry as usize);
+            ptr::copy_nonoverlapping(palette.as_ptr(), pixels.1, palette.len());
+        }
+        Ok(Self { first_index, entry_count, bytes_per_entry, pixels })
+    }
+
+    // Gets the stored entries, empty once they have been released after decoding.
+    pub fn entries(&self) -> &[u8] {
+        if self.pixels.1.is_null() {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(self.pixels.1, self.pixels.0.size()) }
+    }
+
+    // Copies the entry of color map index `index` into the start of `buf`.
+    // Returns Err(ColorMapIndexFailed) if `index` is outside the map or `buf` is shorter
+    // than an entry.
+    #[inline]
+    pub fn try_get_color(&self, buf: &mut [u8], index: u16) -> Result<(), Error> {
+        let index = index
+            .checked_sub(self.first_index)
+            .filter(|i| *i < self.entry_count)
+            .ok_or(Error::ColorMapIndexFailed)?;
+        let entry_size = self.bytes_per_entry as usize;
+        let offset = index as usize * entry_size;
+        let entry = self.entries().get(offset..offset + entry_size).ok_or(Error::ColorMapIndexFailed)?;
+        buf.get_mut(..entry_size).ok_or(Error::ColorMapIndexFailed)?.copy_from_slice(entry);
+        Ok(())
+    }
+}
+
+impl IndexedTga {
+    // Gets the bytes per palette entry.
+    #[inline]
+    pub fn bytes_per_entry(&self) -> usize {
+        TgaHeader::for_format(self.width, self.height, &self.palette_format).get_pixel_size().unwrap_or(1) as usize
+    }
+
+    // Gets the number of palette entries.
+    #[inline]
+    pub fn palette_len(&self) -> usize {
+        self.palette.len() / self.bytes_per_entry()
+    }
+
+    // Gets the palette indices referenced by at least one pixel, in ascending order.
+    pub fn used_colors(&self) -> Vec<u16> {
+        let mut used = [false; 256];
+        for index in &self.indices {
+            used[*index as usize] = true;
+        }
+        (0..256u16).filter(|i| used[*i as usize]).collect()
+    }
+
+    // Removes the palette entries no pixel references and remaps the indices,
+    // keeping the remaining entries in their original order.
+    pub fn compact_palette(&mut self) {
+        let entry_size = self.bytes_per_entry();
+        let used = self.used_colors();
+        let mut remap = [0u8; 256];
+        let mut palette = Vec::with_capacity(used.len() * entry_size);
+        for (new_index, old_index) in used.iter().enumerate() {
+            remap[*old_index as usize] = new_index as u8;
+            let offset = *old_index as usize * entry_size;
+            palette.extend_from_slice(&self.palette[offset..offset + entry_size]);
+        }
+
+        for index in &mut self.indices {
+            *index = remap[*index as usize];
+        }
+        self.palette = palette;
+    }
+
+    // Overwrites the palette entry `index` with `color`, given as stored bytes of the
+    // palette format. Every pixel using the entry is recolored at once.
+    // Returns Err(ColorMapIndexFailed) if `index` is out of the palette and
+    // Err(UnsupportedPixelFormat) if `color` doesn't have the entry size.
+    pub fn set_palette_entry(&mut self, index: u16, color: &[u8]) -> Result<(), Error> {
+        let entry_size = self.bytes_per_entry();
+        if color.len() != entry_size {
+            return Err(Error::UnsupportedPixelFormat);
+        }
+        let offset = index as usize * entry_size;
+        let entry = self.palette.get_mut(offset..offset + entry_size).ok_or(Error::ColorMapIndexFailed)?;
+        entry.copy_from_slice(color);
+        Ok(())
+    }
+
+    // Rotates the `len` palette entries from `start` by `shift` positions: entry
+    // `start + i` moves to `start + (i + shift) mod len`. Negative shifts rotate backwards.
+    // Returns Err(InvalidPaletteSize) if the range isn't inside the palette.
+    pub fn cycle_palette(&mut self, start: u16, len: u16, shift: i16) -> Result<(), Error> {
+        let (start, len) = (start as usize, len as usize);
+        if start + len > self.palette_len() {
+            return Err(Error::InvalidPaletteSize);
+        }
+        if len == 0 {
+            return Ok(());
+        }
+
+        let entry_size = self.bytes_per_entry();
+        let range = &mut self.palette[start * entry_size..(start + len) * entry_size];
+        let steps = (shift as i32).rem_euclid(len as i32) as usize;
+        range.rotate_right(steps * entry_size);
+        Ok(())
+    }
+
+    // Expands `count` animation frames, frame `n` having the range cycled by `n` steps.
+    // The palette itself is left untouched.
+    pub fn cycle_frames(&self, start: u16, len: u16, count: u16) -> Result<Vec<Tga>, Error> {
+        let mut frame = self.clone();
+        let mut frames = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            frames.push(frame.expand()?);
+            frame.cycle_palette(start, len, 1)?;
+        }
+        Ok(frames)
+    }
+
+    // Loads an uncompressed color mapped file (image type 1) without expanding it.
+    // Indices are rebased on `map_first_entry`, so that index 0 is the first stored entry.
+    // Returns Err(UnsupportedImageType) for the other image types.
+    pub fn load(path: &str) -> Result<IndexedTga, Error> {
+        let mut f = File::open(Path::new(path))?;
+        let header = TgaHeader::from_file(&mut f)?;
+        if header.image_type != TgaImageType::ColorMapped as u8 {
+            return Err(Error::UnsupportedImageType);
+        }
+        let palette_format = header.get_pixel_format()?;
+
+        f.seek(SeekFrom::Current(header.id_length as i64))?;
+        let mut palette = vec![0u8; header.map_length as usize * bits_to_bytes(header.map_entry_size.into())];
+        f.read_exact(&mut palette)?;
+        let (width, height) = (header.image_width as usize, header.image_height as usize);
+        let mut indices = vec![0u8; width * height];
+        f.read_exact(&mut indices)?;
+
+        for index in &mut indices {
+            *index = (*index as u16)
+                .checked_sub(header.map_first_entry)
+                .filter(|i| *i < header.map_length)
+                .ok_or(Error::ColorMapIndexFailed)? as u8;
+        }
+        // Same orientation rules as `Tga::new`: keep the origin at the upper left corner.
+        if header.image_descripter & 0x10 != 0 {
+            indices.chunks_exact_mut(width).for_each(|row| row.reverse());
+        }
+        if header.image_descripter & 0x20 == 0 {
+            indices = indices.chunks_exact(width).rev().flatten().copied().collect();
+        }
+
+        Ok(IndexedTga { width: header.image_width, height: header.image_height, palette_format, palette, indices })
+    }
+
+    // Saves the image as an uncompressed color mapped file (image type 1).
+    // Returns Err(InvalidPaletteSize) if the palette is empty or has more than 256 entries,
+    // and Err(UnsupportedPixelFormat) for grayscale palettes which TGA can't store.
+    pub fn save(&self, path: &str) -> Result<(), Error> {
+        self.save_with_options(path, &SaveOptions::default())
+    }
+
+    pub fn save_with_options(&self, path: &str, options: &SaveOptions) -> Result<(), Error> {
+        let entry_bits: u8 = match self.palette_format {
+            TgaPixelFormat::RGB555 => 16,
+            TgaPixelFormat::RGB24 => 24,
+            TgaPixelFormat::ARGB32 => 32,
+            TgaPixelFormat::BW8 | TgaPixelFormat::BW16 => return Err(Error::UnsupportedPixelFormat),
+        };
+        let entries = self.palette_len();
+        if entries == 0 || entries > 256 {
+            return Err(Error::InvalidPaletteSize);
+        }
+
+        let mut header: [u8; HEADER_SIZE] = [0; HEADER_SIZE];
+        header[1] = 1;
+        header[2] = TgaImageType::ColorMapped as u8;
+        header[5] = entries as u8;
+        header[6] = (entries >> 8) as u8;
+        header[7] = entry_bits;
+        header[12] = self.width as u8;
+        header[13] = (self.width >> 8) as u8;
+        header[14] = self.height as u8;
+        header[15] = (self.height >> 8) as u8;
+        header[16] = 8;
+        header[17] = if self.palette_format == TgaPixelFormat::ARGB32 { 0x28 } else { 0x20 };
+
+        let mut f = File::create(path)?;
+        f.write_all(&header)?;
+        f.write_all(&self.palette)?;
+        f.write_all(&self.indices)?;
+        if options.footer {
+            f.write_all(&encode_footer(0, 0))?;
         }
+
         Ok(())
     }
+
+    // Renders the palette as a grid of `swatch` x `swatch` squares, `columns` per row in
+    // index order, for inspecting it. Cells after the last entry are left zeroed.
+    // Returns Err(InvalidImageDimensions) if the grid is empty or too large.
+    pub fn palette_image(&self, swatch: u16, columns: u16) -> Result<Tga, Error> {
+        let entries = self.palette_len();
+        if swatch == 0 || columns == 0 || entries == 0 {
+            return Err(Error::InvalidImageDimensions);
+        }
+        let rows = entries.div_ceil(columns as usize);
+        let width = columns as usize * swatch as usize;
+        let height = rows * swatch as usize;
+        if !check_dimensions(width as u32, height as u32) {
+            return Err(Error::InvalidImageDimensions);
+        }
+
+        let entry_size = self.bytes_per_entry();
+        let mut pixels = vec![0u8; width * height * entry_size];
+        for (i, color) in self.palette.chunks_exact(entry_size).enumerate() {
+            let (cx, cy) = (i % columns as usize * swatch as usize, i / columns as usize * swatch as usize);
+            for y in cy..cy + swatch as usize {
+                let start = (y * width + cx) * entry_size;
+                pixels[start..start + swatch as usize * entry_size]
+                    .chunks_exact_mut(entry_size)
+                    .for_each(|px| px.copy_from_slice(color));
+            }
+        }
+
+        Tga::from_raw(width as u16, height as u16, self.palette_format, &pixels)
+    }
+
+    // Looks every index up in the palette and returns the resulting true color image.
+    // Returns Err(ColorMapIndexFailed) if an index is out of the palette.
+    pub fn expand(&self) -> Result<Tga, Error> {
+        let entry_size = self.bytes_per_entry();
+        let mut pixels = Vec::with_capacity(self.indices.len() * entry_size);
+        for index in &self.indices {
+            let offset = *index as usize * entry_size;
+            let entry = self.palette.get(offset..offset + entry_size).ok_or(Error::ColorMapIndexFailed)?;
+            pixels.extend_from_slice(entry);
+        }
+        Tga::from_raw(self.width, self.height, self.palette_format, &pixels)
+    }
 }
 
 impl Tga {
     pub fn new(path: &str) -> Result<Self, Error> {
+        Self::new_with_options(path, &DecodeOptions::default())
+    }
+
+    pub fn new_with_options(path: &str, options: &DecodeOptions) -> Result<Self, Error> {
         let mut tga_file = File::open(Path::new(path))?;
-        let header = TgaHeader::from_file(&mut tga_file)?;
+        Self::from_reader_with_options(&mut tga_file, options)
+    }
+
+    // Decodes an image held in memory, e.g. received over the network or embedded
+    // with `include_bytes!`, the same way `new` decodes a file.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        Self::from_reader(&mut Cursor::new(data))
+    }
+
+    // Reads the extension area the footer points to, for the image starting at the
+    // current position of `reader`. The position is restored afterwards.
+    // Returns None for files without a footer or extension area, and Err(IllegalHeader)
+    // if the extension area is too short.
+    pub fn read_extension<R: Read + Seek>(reader: &mut R) -> Result<Option<TgaExtension>, Error> {
+        let start = reader.stream_position()?;
+        let result = read_extension_at(reader, start);
+        reader.seek(SeekFrom::Start(start))?;
+        result
+    }
+
+    // Reads the fields of the developer area of the image starting at the reader position,
+    // without decoding it. Files without a TGA 2.0 footer or developer area have none.
+    // The reader is left where it was.
+    pub fn read_developer_fields<R: Read + Seek>(reader: &mut R) -> Result<Vec<DeveloperField>, Error> {
+        let start = reader.stream_position()?;
+        let result = read_developer_fields(reader, start);
+        reader.seek(SeekFrom::Start(start))?;
+        result
+    }
+
+    // Decodes an image from any seekable reader, e.g. a `BufReader` or a `Cursor`.
+    // The image must start at the current position.
+    pub fn from_reader<R: Read + Seek>(reader: &mut R) -> Result<Self, Error> {
+        Self::from_reader_with_options(reader, &DecodeOptions::default())
+    }
+
+    pub fn from_reader_with_options<R: Read + Seek>(tga_file: &mut R, options: &DecodeOptions) -> Result<Self, Error> {
+        let start = tga_file.stream_position()?;
+        let header = TgaHeader::from_file(tga_file)?;
         let info = TgaInfo::from_tga_header(&header)?;
         let image_type = header.is_supported_image_type()?;
+        if options.max_pixels.is_some_and(|max| info.width as usize * info.height as usize > max) {
+            return Err(Error::ImageTooLarge);
+        }
+        // Only `map_length` entries are stored even if `map_first_entry` is not 0.
         let map_size: usize = <u16 as Into<usize>>::into(header.map_length) * bits_to_bytes(header.map_entry_size.into());
         let mut color_map = None;
 
-        // No need to handle the content of the ID field, so skip directly.
-        tga_file.seek(SeekFrom::Current(header.id_length as i64))?;
+        let mut image_id = vec![0u8; header.id_length as usize];
+        tga_file.read_exact(&mut image_id)?;
 
         match image_type {
             TgaImageType::ColorMapped | TgaImageType::RLEColorMapped => {
-                let layptr = unsafe {
-                    let layout = Layout::from_size_align_unchecked(map_size * mem::size_of::<u8>(), mem::size_of::<u8>());
-                    LayPtr {
-                        0: layout.clone(),
-                        1: alloc::alloc(layout)
-                    }
-                };
+                let layptr = LayPtr::alloc(map_size, false)?;
                 color_map = Some(ColorMap {
                     first_index: header.map_first_entry,
                     entry_count: header.map_length,
                     bytes_per_entry: bits_to_bytes(header.map_entry_size.into()) as u8,
                     pixels:  layptr,
                 });
-                if let Err(error) = tga_file.read(unsafe { slice::from_raw_parts_mut(color_map.as_ref().unwrap().pixels.1, color_map.as_ref().unwrap().pixels.0.size()) }) {
-                    return Err(error.into());
-                }
+                tga_file.read_exact(unsafe { slice::from_raw_parts_mut(color_map.as_ref().unwrap().pixels.1, color_map.as_ref().unwrap().pixels.0.size()) })?;
             },
             TgaImageType::TrueColor | TgaImageType::GrayScale | TgaImageType::RLEGrayScale | TgaImageType::RLETrueColor => {
                 // The image is not color mapped at this time, but contains a color map.
@@ -386,140 +1023,1825 @@ impl Tga {
             TgaImageType::NoData => return Err(Error::NoData),
         }
 
-        let data = unsafe {
-            let layout = Layout::from_size_align_unchecked(info.width as usize * info.height as usize * header.get_pixel_size()? as usize, mem::size_of::<u8>());
-            LayPtr(layout.clone(), alloc::alloc(layout))
-        };
+        let data = LayPtr::alloc(image_size(info.width, info.height, header.get_pixel_size()?)?, false)?;
         let mut tga = Self {
             header,
             info,
             data,
             // If it is color mapped, 'map' is Some(ColorMap), otherwise it's None.
             map: color_map,
+            image_id,
+            premultiplied: false,
+            rgba_order: false,
+            indexed: false,
+            extension: None,
+            developer_fields: Vec::new(),
         };
 
         // Decode data
-        tga.decode_data(&mut tga_file)?;
-        // Release color_map's pixels.
-        if let Some(ref mut cm) = tga.map {
-            unsafe {
-                alloc::dealloc(cm.pixels.1, cm.pixels.0);
-                cm.pixels.1 = ptr::null_mut();
-            }
+        tga.decode_data(tga_file, options.rle_mode)?;
+        // Release color_map's pixels, unless asked to keep them.
+        if let Some(ref mut cm) = tga.map.as_mut().filter(|_| !options.keep_color_map) {
+            // Dropping the old pointer frees it, unless the map is empty.
+            let released = LayPtr(cm.pixels.0, ptr::null_mut());
+            drop(mem::replace(&mut cm.pixels, released));
         }
 
+        // The decoded data is kept with the origin at the upper left corner, which is
+        // also what `save` declares. Bit 4 means right-to-left, bit 5 means top-to-bottom.
         if tga.header.image_descripter & 0x10 != 0 {
             tga.image_flip_h()?;
         }
 
-        if tga.header.image_descripter & 0x20 != 0 {
+        if tga.header.image_descripter & 0x20 == 0 {
             tga.image_flip_v()?;
         }
 
+        // A malformed extension area doesn't prevent using the pixels.
+        tga_file.seek(SeekFrom::Start(start))?;
+        tga.extension = Self::read_extension(tga_file).ok().flatten();
+        tga.developer_fields = read_developer_fields(tga_file, start).unwrap_or_default();
+
+        if options.verify_crc {
+            if let Some(stored) = read_developer_entry(tga_file, start, CRC_DEVELOPER_TAG)? {
+                if stored.len() != 4 || u32::from_le_bytes([stored[0], stored[1], stored[2], stored[3]]) != crc32(tga.pixels()) {
+                    return Err(Error::ChecksumMismatch);
+                }
+            }
+        }
+
         Ok(tga)
     }
 
     pub fn save(&self, path: &str) -> Result<(), Error> {
-        let pixel_size = self.header.get_pixel_size()?;
-        let mut header: [u8; HEADER_SIZE] = [0; HEADER_SIZE];
+        self.save_with_options(path, &SaveOptions::default())
+    }
+
+    pub fn save_with_options(&self, path: &str, options: &SaveOptions) -> Result<(), Error> {
         let mut f = File::create(path)?;
-        header[12] = self.info.width as u8 & 0xff;
-        header[13] = (self.info.width >> 8) as u8 & 0xff;
-        header[14] = self.info.height as u8 & 0xff;
-        header[15] = (self.info.height >> 8) as u8 & 0xff;
-        header[16] = (pixel_size * 8) as u8;
-        match self.info.pixel_format {
-            TgaPixelFormat::BW8 | TgaPixelFormat::BW16 => { header[2] = TgaImageType::GrayScale as u8 },
-            _ => { header[2] = TgaImageType::TrueColor as u8 },
-        }
+        self.write_to_with_options(&mut f, options)
+    }
 
-        match self.info.pixel_format {
-            TgaPixelFormat::ARGB32 => { header[17] = 0x28 },
-            _ => { header[17] = 0x20 },
-        }
+    // Serializes the image as `save` would write it.
+    pub fn to_vec(&self) -> Result<Vec<u8>, Error> {
+        let mut buf = Vec::with_capacity(HEADER_SIZE + self.image_id.len() + self.data.0.size() + FOOTER_SIZE);
+        self.write_to(&mut buf)?;
+        Ok(buf)
+    }
+
+    // Serializes the image into an in-memory cursor positioned at 0, for libraries
+    // that want a `Read + Seek` source.
+    pub fn to_cursor(&self) -> Result<Cursor<Vec<u8>>, Error> {
+        Ok(Cursor::new(self.to_vec()?))
+    }
+
+    // Serializes the image into any writer, e.g. a `Vec<u8>`, a socket or a compressor.
+    // `save` is this on a newly created file. Every byte is written or an error returned.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> Result<(), Error> {
+        self.write_to_with_options(w, &SaveOptions::default())
+    }
+
+    pub fn write_to_with_options<W: Write>(&self, w: &mut W, options: &SaveOptions) -> Result<(), Error> {
+        debug_assert!(self.validate_against_header().is_ok(), "header and info out of sync");
+        let header = self.encode_header(false)?;
         // Save the tga image header.
-        f.write(&header)?;
+        w.write_all(&header)?;
+        w.write_all(&self.image_id)?;
+        w.write_all(self.stored_color_map())?;
         // Save the main data.
-        unsafe {
-            let buf = slice::from_raw_parts_mut(self.data.1, self.data.0.size());
-            f.write(buf)?;
+        w.write_all(self.pixels())?;
+        if options.footer {
+            w.write_all(&encode_footer(0, 0))?;
         }
 
         Ok(())
     }
 
-    pub fn image_flip_h(&mut self) -> Result<(), Error> {
-        if self.data.0.size() <= 0 {
-            return Err(Error::NoData);
+    // Saves the image as an uncompressed color mapped file (image type 1) using `map` as
+    // palette, e.g. a color mapped file decoded with `DecodeOptions::keep_color_map`.
+    // Every pixel is written as the index of the first entry holding its color.
+    // Returns Err(NoData) if the image has no color map, Err(UnsupportedPixelFormat) if
+    // the entries don't have the pixel size and Err(ColorMapIndexFailed) if a color is
+    // missing from the map or its index doesn't fit in 8 bits.
+    pub fn save_colormapped(&self, path: &str) -> Result<(), Error> {
+        self.save_colormapped_with_options(path, &SaveOptions::default())
+    }
+
+    pub fn save_colormapped_with_options(&self, path: &str, options: &SaveOptions) -> Result<(), Error> {
+        let map = self.map.as_ref().filter(|map| !map.entries().is_empty()).ok_or(Error::NoData)?;
+        if self.indexed {
+            return self.save_with_options(path, options);
+        }
+        let pixel_size = self.pixel_size()? as usize;
+        if map.bytes_per_entry as usize != pixel_size || self.info.pixel_format == TgaPixelFormat::BW16 {
+            return Err(Error::UnsupportedPixelFormat);
         }
 
-        let pixel_size = self.header.get_pixel_size().unwrap() as usize;
-        let flip_num = <u16 as Into<usize>>::into(self.info.width) / 2;
-        let image_height: usize = self.info.height.into();
-        let image_width: usize = self.info.width.into();
+        let mut lookup = HashMap::new();
+        for (i, entry) in map.entries().chunks_exact(pixel_size).enumerate().rev() {
+            lookup.insert(entry, map.first_index as usize + i);
+        }
+        let indices = self.pixels()
+            .chunks_exact(pixel_size)
+            .map(|px| match lookup.get(px) {
+                Some(index) if *index <= u8::MAX as usize => Ok(*index as u8),
+                _ => Err(Error::ColorMapIndexFailed),
+            })
+            .collect::<Result<Vec<u8>, Error>>()?;
+
+        let mut header = self.encode_header(false)?;
+        header[1] = 1;
+        header[2] = TgaImageType::ColorMapped as u8;
+        header[3..5].copy_from_slice(&map.first_index.to_le_bytes());
+        header[5..7].copy_from_slice(&map.entry_count.to_le_bytes());
+        header[7] = map.bytes_per_entry * 8;
+        header[16] = 8;
 
-        unsafe {
-            let layout = Layout::from_size_align_unchecked(pixel_size * mem::size_of::<u8>(), mem::size_of::<u8>());
-            let ptr = alloc::alloc(layout);
-            for i in 0..flip_num {
-                for j in 0..image_height {
-                    // Swap two pixels.
-                    // origin at the upper left corner
-                    let p1 = self.get_pixel(i as i32, j as i32);
-                    let p2 = self.get_pixel((image_width - 1 - i) as i32, j as i32);
-                    ptr::copy_nonoverlapping(p1, ptr, pixel_size * mem::size_of::<u8>());
-                    ptr::copy_nonoverlapping(p2, p1, pixel_size * mem::size_of::<u8>());
-                    ptr::copy_nonoverlapping(ptr, p2, pixel_size * mem::size_of::<u8>());
-                }
-            }
-            alloc::dealloc(ptr, layout);
+        let mut f = File::create(path)?;
+        f.write_all(&header)?;
+        f.write_all(&self.image_id)?;
+        f.write_all(map.entries())?;
+        f.write_all(&indices)?;
+        if options.footer {
+            f.write_all(&encode_footer(0, 0))?;
         }
-        
+
         Ok(())
     }
 
-    pub fn image_flip_v(&mut self) -> Result<(), Error> {
-        if self.data.0.size() <= 0 {
-            return Err(Error::NoData);
+    // Same as `save`, but writes the pixels `chunk_bytes` at a time, flushing after each
+    // chunk and calling `progress` with the total bytes written so far.
+    pub fn save_chunked<F: FnMut(usize)>(&self, path: &str, chunk_bytes: usize, mut progress: F) -> Result<(), Error> {
+        let mut f = File::create(path)?;
+        let header = self.encode_header(false)?;
+        f.write_all(&header)?;
+        f.write_all(&self.image_id)?;
+        f.write_all(self.stored_color_map())?;
+        let mut written = header.len() + self.image_id.len() + self.stored_color_map().len();
+        progress(written);
+
+        for chunk in self.pixels().chunks(chunk_bytes.max(1)) {
+            f.write_all(chunk)?;
+            f.flush()?;
+            written += chunk.len();
+            progress(written);
         }
+        f.write_all(&encode_footer(0, 0))?;
+        progress(written + FOOTER_SIZE);
 
-        let pixel_size = self.header.get_pixel_size().unwrap() as usize;
-        let flip_num = <u16 as Into<usize>>::into(self.info.width) / 2;
-        let image_height: usize = self.info.height.into();
-        let image_width: usize = self.info.width.into();
-
-        unsafe {
-            let layout = Layout::from_size_align_unchecked(pixel_size * mem::size_of::<u8>(), mem::size_of::<u8>());
-            let ptr = alloc::alloc(layout);
-            for i in 0..flip_num {
-                for j in 0..image_width {
-                    // Swap two pixels.
-                    // origin at the upper left corner
-                    let p1 = self.get_pixel(j as i32, i as i32);
-                    let p2 = self.get_pixel(j as i32, (image_height - 1 - i) as i32);
-                    ptr::copy_nonoverlapping(p1, ptr, pixel_size * mem::size_of::<u8>());
-                    ptr::copy_nonoverlapping(p2, p1, pixel_size * mem::size_of::<u8>());
-                    ptr::copy_nonoverlapping(ptr, p2, pixel_size * mem::size_of::<u8>());
-                }
-            }
-            alloc::dealloc(ptr, layout);
-        }
-        
         Ok(())
     }
 
-    #[inline]
-    fn get_pixel(&self, mut x: i32, mut y: i32) -> *mut u8 {
-        if x < 0 {
-            x = 0;
-        } else if x >= self.info.width as i32{
-            x = self.info.width as i32 - 1;
-        }
+    // Same as `save`, followed by a CRC32 of the pixels stored as a developer area entry
+    // and a TGA 2.0 footer pointing to it. Decoding with `DecodeOptions::verify_crc`
+    // detects corrupted pixels.
+    pub fn save_with_crc(&self, path: &str) -> Result<(), Error> {
+        let mut bytes = Vec::new();
+        self.write_to_with_options(&mut bytes, &SaveOptions { footer: false })?;
+        let crc_offset = bytes.len() as u32;
+        bytes.extend_from_slice(&crc32(self.pixels()).to_le_bytes());
+
+        // A developer directory with a single tag, then the footer.
+        let directory_offset = bytes.len() as u32;
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&CRC_DEVELOPER_TAG.to_le_bytes());
+        bytes.extend_from_slice(&crc_offset.to_le_bytes());
+        bytes.extend_from_slice(&4u32.to_le_bytes());
+        bytes.extend_from_slice(&encode_footer(0, directory_offset));
+
+        fs::write(path, bytes)?;
+        Ok(())
+    }
 
-        if y < 0 {
-            y = 0;
-        } else if y >= self.info.width as i32{
-            y = self.info.height as i32 - 1;
+    // Same as `save`, but creates the missing parent directories first.
+    pub fn save_create_dirs(&self, path: &str) -> Result<(), Error> {
+        if let Some(parent) = Path::new(path).parent() {
+            fs::create_dir_all(parent)?;
+        }
+        self.save(path)
+    }
+
+    // Gets the heap bytes held by the image: the pixel data, the retained color map
+    // and the image ID.
+    pub fn memory_usage(&self) -> usize {
+        let map_size = match &self.map {
+            Some(map) if !map.pixels.1.is_null() => map.pixels.0.size(),
+            _ => 0,
+        };
+        self.data.0.size() + map_size + self.image_id.capacity()
+    }
+
+    // Gets where the pixel data begins in the file described by the header, i.e. after
+    // the header, the image ID and the color map.
+    pub fn data_offset(&self) -> u64 {
+        let map_size = self.header.map_length as u64 * bits_to_bytes(self.header.map_entry_size.into()) as u64;
+        HEADER_SIZE as u64 + self.header.id_length as u64 + map_size
+    }
+
+    // Sets the image ID field written by `save`.
+    // Returns Err(ImageIdTooLong) if `id` is longer than 255 bytes.
+    pub fn set_image_id(&mut self, id: &[u8]) -> Result<(), Error> {
+        if id.len() > u8::MAX as usize {
+            return Err(Error::ImageIdTooLong);
+        }
+        self.image_id = id.to_vec();
+        self.header.id_length = id.len() as u8;
+        Ok(())
+    }
+
+    // Saves the image with run-length encoded data (image type 10 or 11, or 9 for indexed
+    // images whose indices are encoded). `RleMode::PerScanline` (the default) keeps packets within a scanline as the
+    // specification recommends. Packets hold at most 128 pixels.
+    pub fn save_rle(&self, path: &str, mode: RleMode) -> Result<(), Error> {
+        self.save_rle_with_options(path, mode, &SaveOptions::default())
+    }
+
+    pub fn save_rle_with_options(&self, path: &str, mode: RleMode, options: &SaveOptions) -> Result<(), Error> {
+        let header = self.encode_header(true)?;
+        let pixel_size = self.stored_pixel_size()? as usize;
+        let run_size = match mode {
+            RleMode::PerScanline => self.dimensions().0 as usize * pixel_size,
+            RleMode::Continuous => self.data.0.size(),
+        };
+
+        let mut encoded = Vec::with_capacity(self.data.0.size());
+        for run in self.pixels().chunks(run_size) {
+            encode_rle_packets(run, pixel_size, &mut encoded);
+        }
+
+        let mut f = File::create(path)?;
+        f.write_all(&header)?;
+        f.write_all(&self.image_id)?;
+        f.write_all(self.stored_color_map())?;
+        f.write_all(&encoded)?;
+        if options.footer {
+            f.write_all(&encode_footer(0, 0))?;
+        }
+
+        Ok(())
+    }
+
+    // Builds the header written by `save` and `save_rle`. Indexed images get a color
+    // mapped header, see `stored_color_map`.
+    fn encode_header(&self, rle: bool) -> Result<[u8; HEADER_SIZE], Error> {
+        self.require_file_order()?;
+        let pixel_size = self.stored_pixel_size()?;
+        let (width, height) = self.dimensions();
+        let mut header: [u8; HEADER_SIZE] = [0; HEADER_SIZE];
+        header[0] = self.image_id.len() as u8;
+        header[12] = width as u8;
+        header[13] = (width >> 8) as u8;
+        header[14] = height as u8;
+        header[15] = (height >> 8) as u8;
+        header[16] = (pixel_size * 8) as u8;
+        match self.info.pixel_format {
+            _ if self.indexed => {
+                let map = self.map.as_ref().ok_or(Error::NoData)?;
+                header[1] = 1;
+                header[2] = TgaImageType::ColorMapped as u8;
+                header[3..5].copy_from_slice(&map.first_index.to_le_bytes());
+                header[5..7].copy_from_slice(&map.entry_count.to_le_bytes());
+                header[7] = map.bytes_per_entry * 8;
+            },
+            TgaPixelFormat::BW8 | TgaPixelFormat::BW16 => { header[2] = TgaImageType::GrayScale as u8 },
+            _ => { header[2] = TgaImageType::TrueColor as u8 },
+        }
+
+        header[17] = 0x20 | (self.info.attribute_bits & 0x0F);
+
+        if rle {
+            // RLE image types are the uncompressed ones plus 8.
+            header[2] += 8;
+        }
+
+        Ok(header)
+    }
+
+    pub fn image_flip_h(&mut self) -> Result<(), Error> {
+        if self.data.0.size() == 0 {
+            return Err(Error::NoData);
+        }
+
+        let pixel_size = self.pixel_size()? as usize;
+        let (width, height) = self.dimensions();
+        let flip_num = <u16 as Into<usize>>::into(width) / 2;
+        let image_height: usize = height.into();
+        let image_width: usize = width.into();
+
+        // Temporary pixel used for swapping, released when dropped.
+        let temp = LayPtr::alloc(image_size(1, 1, pixel_size as u32)?, false)?;
+        let ptr = temp.1;
+
+        unsafe {
+            for i in 0..flip_num {
+                for j in 0..image_height {
+                    // Swap two pixels.
+                    // origin at the upper left corner
+                    let p1 = self.get_pixel(i as i32, j as i32);
+                    let p2 = self.get_pixel((image_width - 1 - i) as i32, j as i32);
+                    ptr::copy_nonoverlapping(p1, ptr, pixel_size * mem::size_of::<u8>());
+                    ptr::copy_nonoverlapping(p2, p1, pixel_size * mem::size_of::<u8>());
+                    ptr::copy_nonoverlapping(ptr, p2, pixel_size * mem::size_of::<u8>());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn image_flip_v(&mut self) -> Result<(), Error> {
+        if self.data.0.size() == 0 {
+            return Err(Error::NoData);
+        }
+
+        let pixel_size = self.pixel_size()? as usize;
+        let (width, height) = self.dimensions();
+        let flip_num = <u16 as Into<usize>>::into(height) / 2;
+        let image_height: usize = height.into();
+        let image_width: usize = width.into();
+
+        // Temporary pixel used for swapping, released when dropped.
+        let temp = LayPtr::alloc(image_size(1, 1, pixel_size as u32)?, false)?;
+        let ptr = temp.1;
+
+        unsafe {
+            for i in 0..flip_num {
+                for j in 0..image_width {
+                    // Swap two pixels.
+                    // origin at the upper left corner
+                    let p1 = self.get_pixel(j as i32, i as i32);
+                    let p2 = self.get_pixel(j as i32, (image_height - 1 - i) as i32);
+                    ptr::copy_nonoverlapping(p1, ptr, pixel_size * mem::size_of::<u8>());
+                    ptr::copy_nonoverlapping(p2, p1, pixel_size * mem::size_of::<u8>());
+                    ptr::copy_nonoverlapping(ptr, p2, pixel_size * mem::size_of::<u8>());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Guards operations combining two images, which need equal dimensions.
+    // Returns Err(DimensionMismatch) with the dimensions of both images otherwise.
+    #[inline]
+    pub fn ensure_same_dimensions(&self, other: &Tga) -> Result<(), Error> {
+        match self.dimensions() == other.dimensions() {
+            true => Ok(()),
+            false => Err(Error::DimensionMismatch { expected: self.dimensions(), got: other.dimensions() }),
+        }
+    }
+
+    // Guards transforms that only handle some pixel formats.
+    // Returns Err(UnsupportedPixelFormat) if the image format isn't one of `allowed`,
+    // or if the image is indexed as its data isn't in that format.
+    #[inline]
+    pub fn require_format(&self, allowed: &[TgaPixelFormat]) -> Result<(), Error> {
+        match allowed.contains(&self.info.pixel_format) && !self.indexed {
+            true => Ok(()),
+            false => Err(Error::UnsupportedPixelFormat),
+        }
+    }
+
+    // Views the pixels of an ARGB32 image as 4 bytes chunks without copying.
+    // The bytes of each chunk are in stored order, i.e. B, G, R, A.
+    pub fn as_rgba_chunks(&self) -> Result<&[[u8; 4]], Error> {
+        self.require_format(&[TgaPixelFormat::ARGB32])?;
+        let (chunks, _) = self.pixels().as_chunks::<4>();
+        Ok(chunks)
+    }
+
+    // Gets the samples of a grayscale image as native 16 bits values.
+    // BW16 samples are read from their little-endian bytes, BW8 samples are scaled up.
+    pub fn into_luma16(&self) -> Result<Vec<u16>, Error> {
+        match self.info.pixel_format {
+            TgaPixelFormat::BW16 => Ok(self.pixels()
+                .chunks_exact(2)
+                .map(|px| u16::from_le_bytes([px[0], px[1]]))
+                .collect()),
+            TgaPixelFormat::BW8 => Ok(self.pixels().iter().map(|v| *v as u16 * 257).collect()),
+            _ => Err(Error::UnsupportedPixelFormat),
+        }
+    }
+
+    // Changes the declared pixel format without touching the data, e.g. for mis-tagged files.
+    // The new format must have the same bytes per pixel as the current one, otherwise
+    // Err(UnsupportedPixelFormat) is returned.
+    pub fn reinterpret_format(&mut self, format: TgaPixelFormat) -> Result<(), Error> {
+        let pixel_size = self.pixel_size()?;
+        let new_pixel_size = TgaHeader::for_format(self.info.width, self.info.height, &format).get_pixel_size()?;
+        if pixel_size != new_pixel_size {
+            return Err(Error::UnsupportedPixelFormat);
+        }
+        self.set_format(format);
+        Ok(())
+    }
+
+    // Detects edges with a 3x3 Sobel operator applied to the luminance of the image.
+    // Returns a BW8 image of the gradient magnitude, border pixels are clamped.
+    pub fn sobel_edges(&self) -> Result<Tga, Error> {
+        let width = self.info.width as usize;
+        let height = self.info.height as usize;
+        let gray = self.luma_plane()?;
+        let mut edges = vec![0u8; width * height];
+
+        for y in 0..height {
+            for x in 0..width {
+                let at = |dx: isize, dy: isize| -> i32 {
+                    let sx = (x as isize + dx).clamp(0, width as isize - 1) as usize;
+                    let sy = (y as isize + dy).clamp(0, height as isize - 1) as usize;
+                    gray[sy * width + sx] as i32
+                };
+                let gx = at(1, -1) + 2 * at(1, 0) + at(1, 1) - at(-1, -1) - 2 * at(-1, 0) - at(-1, 1);
+                let gy = at(-1, 1) + 2 * at(0, 1) + at(1, 1) - at(-1, -1) - 2 * at(0, -1) - at(1, -1);
+                let magnitude = ((gx * gx + gy * gy) as f32).sqrt();
+                edges[y * width + x] = magnitude.min(255.0) as u8;
+            }
+        }
+
+        Tga::from_raw(self.info.width, self.info.height, TgaPixelFormat::BW8, &edges)
+    }
+
+    // Makes an image of the given dimensions and format with all bytes set to zero,
+    // to be drawn into afterwards. The buffer is allocated once with its final size.
+    pub fn new_uninit(info: TgaInfo) -> Result<Tga, Error> {
+        if !check_dimensions(info.width.into(), info.height.into()) {
+            return Err(Error::InvalidImageDimensions);
+        }
+
+        let header = TgaHeader::for_format(info.width, info.height, &info.pixel_format);
+        let size = image_size(info.width, info.height, header.get_pixel_size()?)?;
+        let data = LayPtr::alloc(size, true)?;
+
+        Ok(Self {
+            header,
+            info,
+            data,
+            map: None,
+            image_id: Vec::new(),
+            premultiplied: false,
+            rgba_order: false,
+            indexed: false,
+            extension: None,
+            developer_fields: Vec::new(),
+        })
+    }
+
+    // Makes an ARGB32 image from pixels given in R, G, B, A order.
+    // `data` must hold exactly `width * height * 4` bytes.
+    pub fn new_rgba8(width: u16, height: u16, mut data: Vec<u8>) -> Result<Tga, Error> {
+        if data.len() != width as usize * height as usize * 4 {
+            return Err(Error::InvalidImageDimensions);
+        }
+        // Stored as BGRA.
+        data.chunks_exact_mut(4).for_each(|px| px.swap(0, 2));
+        Tga::from_raw(width, height, TgaPixelFormat::ARGB32, &data)
+    }
+
+    // Makes a RGB24 image from pixels given in R, G, B order.
+    // `data` must hold exactly `width * height * 3` bytes.
+    pub fn new_rgb8(width: u16, height: u16, mut data: Vec<u8>) -> Result<Tga, Error> {
+        if data.len() != width as usize * height as usize * 3 {
+            return Err(Error::InvalidImageDimensions);
+        }
+        // Stored as BGR.
+        data.chunks_exact_mut(3).for_each(|px| px.swap(0, 2));
+        Tga::from_raw(width, height, TgaPixelFormat::RGB24, &data)
+    }
+
+    // Makes a BW8 image, `data` must hold exactly `width * height` bytes.
+    pub fn new_gray8(width: u16, height: u16, data: Vec<u8>) -> Result<Tga, Error> {
+        if data.len() != width as usize * height as usize {
+            return Err(Error::InvalidImageDimensions);
+        }
+        Tga::from_raw(width, height, TgaPixelFormat::BW8, &data)
+    }
+
+    // Assembles an image from scanlines produced one at a time, top to bottom.
+    // Each row must hold `width` pixels of `info.pixel_format` and there must be `height` rows.
+    pub fn from_rows<I: IntoIterator<Item = Vec<u8>>>(info: TgaInfo, rows: I) -> Result<Tga, Error> {
+        let pixel_size = TgaHeader::for_format(info.width, info.height, &info.pixel_format).get_pixel_size()? as usize;
+        let row_size = info.width as usize * pixel_size;
+        let mut pixels = Vec::with_capacity(row_size * info.height as usize);
+        let mut row_count = 0;
+
+        for row in rows {
+            if row.len() != row_size || row_count == info.height {
+                return Err(Error::InvalidImageDimensions);
+            }
+            pixels.extend_from_slice(&row);
+            row_count += 1;
+        }
+        if row_count != info.height {
+            return Err(Error::InvalidImageDimensions);
+        }
+
+        Tga::from_raw(info.width, info.height, info.pixel_format, &pixels)
+    }
+
+    // Applies `f` to the alpha channel of every pixel in place, colors are left untouched.
+    // Only ARGB32 images carry an alpha channel.
+    pub fn map_alpha<F: FnMut(u8) -> u8>(&mut self, mut f: F) -> Result<(), Error> {
+        self.require_format(&[TgaPixelFormat::ARGB32])?;
+        for px in self.pixels_mut().chunks_exact_mut(4) {
+            px[3] = f(px[3]);
+        }
+        Ok(())
+    }
+
+    // Makes a BW8 mask where pixels with a luminance of at least `level` are 255, others 0.
+    pub fn threshold(&self, level: u8) -> Result<Tga, Error> {
+        let mask: Vec<u8> = self.luma_plane()?
+            .iter()
+            .map(|v| if *v >= level { 255 } else { 0 })
+            .collect();
+        Tga::from_raw(self.info.width, self.info.height, TgaPixelFormat::BW8, &mask)
+    }
+
+    // Convolves the 8 bits channels with a `kw` x `kh` kernel given in row-major order, both
+    // sizes being odd. Each result is divided by `divisor`, offset by `bias` and saturated.
+    // Samples outside the image are clamped to the border, alpha is left untouched.
+    pub fn convolve(&mut self, kernel: &[f32], kw: usize, kh: usize, divisor: f32, bias: f32) -> Result<(), Error> {
+        self.require_format(&[TgaPixelFormat::BW8, TgaPixelFormat::RGB24, TgaPixelFormat::ARGB32])?;
+        if kw.is_multiple_of(2) || kh.is_multiple_of(2) || kernel.len() != kw * kh || divisor == 0.0 {
+            return Err(Error::InvalidKernel);
+        }
+
+        let channels = if self.info.pixel_format == TgaPixelFormat::BW8 { 1 } else { 3 };
+        let pixel_size = self.pixel_size()? as usize;
+        let width = self.info.width as isize;
+        let height = self.info.height as isize;
+        let (rx, ry) = ((kw / 2) as isize, (kh / 2) as isize);
+        let source = self.pixels().to_vec();
+        let data = self.pixels_mut();
+
+        for y in 0..height {
+            for x in 0..width {
+                for c in 0..channels {
+                    let mut sum = 0.0;
+                    for (k, weight) in kernel.iter().enumerate() {
+                        let sx = (x + (k % kw) as isize - rx).clamp(0, width - 1);
+                        let sy = (y + (k / kw) as isize - ry).clamp(0, height - 1);
+                        sum += weight * source[(sy * width + sx) as usize * pixel_size + c] as f32;
+                    }
+                    data[(y * width + x) as usize * pixel_size + c] = (sum / divisor + bias).round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Computes a signed distance field from a BW8 mask, pixels of 128 and above are inside.
+    // The distance to the edge is divided by `spread` and mapped so that the edge is 0.5,
+    // inside is brighter and outside is darker. Returns a BW8 image.
+    pub fn signed_distance_field(&self, spread: f32) -> Result<Tga, Error> {
+        self.require_format(&[TgaPixelFormat::BW8])?;
+        let width = self.info.width as usize;
+        let height = self.info.height as usize;
+        let inside: Vec<bool> = self.pixels().iter().map(|v| *v >= 128).collect();
+        let outside: Vec<bool> = inside.iter().map(|v| !v).collect();
+        let to_outside = distance_transform(&outside, width, height);
+        let to_inside = distance_transform(&inside, width, height);
+
+        let field: Vec<u8> = (0..width * height)
+            .map(|i| {
+                // The edge lies half way between an inside and an outside pixel.
+                let distance = match inside[i] {
+                    true => to_outside[i] - 0.5,
+                    false => 0.5 - to_inside[i],
+                };
+                ((0.5 + distance / (2.0 * spread)).clamp(0.0, 1.0) * 255.0).round() as u8
+            })
+            .collect();
+
+        Tga::from_raw(self.info.width, self.info.height, TgaPixelFormat::BW8, &field)
+    }
+
+    // Compares the color channels of two images of equal dimensions, ignoring alpha.
+    // Pixels are converted to RGB first, so the formats may differ.
+    // Returns Err(UnsupportedPixelFormat) if either image was swapped by `to_rgba`.
+    pub fn rgb_equals(&self, other: &Tga) -> Result<bool, Error> {
+        self.ensure_same_dimensions(other)?;
+        self.require_file_order()?;
+        other.require_file_order()?;
+
+        let pixel_size = self.pixel_size()? as usize;
+        let other_pixel_size = other.pixel_size()? as usize;
+        Ok(self.pixels()
+            .chunks_exact(pixel_size)
+            .zip(other.pixels().chunks_exact(other_pixel_size))
+            .all(|(a, b)| {
+                pixel_to_rgba(&self.info.pixel_format, a)[..3] == pixel_to_rgba(&other.info.pixel_format, b)[..3]
+            }))
+    }
+
+    // Computes a CRC-32 per scanline, top to bottom. Comparing the checksums of two
+    // images tells which rows changed, e.g. to only stream those.
+    pub fn row_checksums(&self) -> Vec<u32> {
+        self.rows_iter().map(crc32).collect()
+    }
+
+    // Samples the image as a texture at (u, v) with bilinear filtering, (0, 0) being the
+    // upper left corner and (1, 1) the lower right one. Texel centers lie at (i + 0.5) / size.
+    // Returns the color in R, G, B, A order, or None if a coordinate is not finite or the
+    // pixels were swapped by `to_rgba`.
+    pub fn sample(&self, u: f32, v: f32, wrap: WrapMode) -> Option<[u8; 4]> {
+        if !u.is_finite() || !v.is_finite() {
+            return None;
+        }
+
+        let x = u * self.info.width as f32 - 0.5;
+        let y = v * self.info.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let wrap_x = |i: i64| wrap_coordinate(i, self.info.width as i64, wrap);
+        let wrap_y = |i: i64| wrap_coordinate(i, self.info.height as i64, wrap);
+        let (xa, xb) = (wrap_x(x0 as i64), wrap_x(x0 as i64 + 1));
+        let (ya, yb) = (wrap_y(y0 as i64), wrap_y(y0 as i64 + 1));
+
+        let corners = [
+            self.rgba_at(xa, ya)?,
+            self.rgba_at(xb, ya)?,
+            self.rgba_at(xa, yb)?,
+            self.rgba_at(xb, yb)?,
+        ];
+        let mut color = [0u8; 4];
+        for (c, value) in color.iter_mut().enumerate() {
+            let top = corners[0][c] as f32 * (1.0 - fx) + corners[1][c] as f32 * fx;
+            let bottom = corners[2][c] as f32 * (1.0 - fx) + corners[3][c] as f32 * fx;
+            *value = (top * (1.0 - fy) + bottom * fy).round() as u8;
+        }
+        Some(color)
+    }
+
+    // Repeats the image over a canvas of `out_w` x `out_h` pixels, starting at the upper
+    // left corner and wrapping at the edges of the image.
+    pub fn tile(&self, out_w: u16, out_h: u16) -> Result<Tga, Error> {
+        if !check_dimensions(out_w.into(), out_h.into()) {
+            return Err(Error::InvalidImageDimensions);
+        }
+
+        let pixel_size = self.pixel_size()? as usize;
+        let width = self.info.width as usize;
+        let height = self.info.height as usize;
+        let mut pixels = Vec::with_capacity(image_size(out_w, out_h, pixel_size as u32)?);
+        for y in 0..out_h as usize {
+            let row = &self.pixels()[(y % height) * width * pixel_size..][..width * pixel_size];
+            for x in 0..out_w as usize {
+                let offset = (x % width) * pixel_size;
+                pixels.extend_from_slice(&row[offset..offset + pixel_size]);
+            }
+        }
+
+        Tga::from_raw(out_w, out_h, self.info.pixel_format, &pixels)
+    }
+
+    // Changes the canvas to `new_w` x `new_h` pixels without scaling: the image is
+    // placed according to `anchor`, cropped where it overflows and padded with `fill`,
+    // given as stored bytes of the image format.
+    pub fn resize_canvas(&self, new_w: u16, new_h: u16, anchor: Anchor, fill: &[u8]) -> Result<Tga, Error> {
+        let pixel_size = self.pixel_size()? as usize;
+        if fill.len() != pixel_size {
+            return Err(Error::UnsupportedPixelFormat);
+        }
+        if !check_dimensions(new_w.into(), new_h.into()) {
+            return Err(Error::InvalidImageDimensions);
+        }
+
+        let (width, height) = self.dimensions();
+        let (ox, oy) = anchor.offset(new_w as i32 - width as i32, new_h as i32 - height as i32);
+        let mut pixels = fill.repeat(new_w as usize * new_h as usize);
+        for y in 0..new_h as i32 {
+            let sy = y - oy;
+            if sy < 0 || sy >= height as i32 {
+                continue;
+            }
+            // The columns of the row covered by the image.
+            let x0 = ox.max(0);
+            let x1 = (ox + width as i32).min(new_w as i32);
+            if x0 >= x1 {
+                continue;
+            }
+            let src = (sy as usize * width as usize + (x0 - ox) as usize) * pixel_size;
+            let dst = (y as usize * new_w as usize + x0 as usize) * pixel_size;
+            let len = (x1 - x0) as usize * pixel_size;
+            pixels[dst..dst + len].copy_from_slice(&self.pixels()[src..src + len]);
+        }
+
+        Tga::from_raw(new_w, new_h, self.info.pixel_format, &pixels)
+    }
+
+    // Scales the image to `new_w` x `new_h` pixels, copying the closest source pixel.
+    // Returns Err(InvalidImageDimensions) if the new dimensions aren't valid.
+    pub fn resize_nearest(&mut self, new_w: u16, new_h: u16) -> Result<(), Error> {
+        let resized = self.scaled(new_w, new_h, ScaleFilter::Nearest)?;
+        self.data = resized.data;
+        self.set_dimensions(new_w, new_h);
+        Ok(())
+    }
+
+    // Scales the image to `new_w` x `new_h` pixels, interpolating the four closest source
+    // pixels per channel. RGB555 and BW16 images are scaled as `resize_nearest` does.
+    // Returns Err(InvalidImageDimensions) if the new dimensions aren't valid.
+    pub fn resize_bilinear(&mut self, new_w: u16, new_h: u16) -> Result<(), Error> {
+        let resized = self.scaled(new_w, new_h, ScaleFilter::Bilinear)?;
+        self.data = resized.data;
+        self.set_dimensions(new_w, new_h);
+        Ok(())
+    }
+
+    // Scales the image so that its longer side is `max_dim` pixels, keeping the aspect
+    // ratio. The shorter side is rounded to the nearest integer, at least 1.
+    pub fn thumbnail_fit(&self, max_dim: u16, filter: ScaleFilter) -> Result<Tga, Error> {
+        let (width, height) = self.dimensions();
+        let fit = |short: u16, long: u16| {
+            ((short as u32 * max_dim as u32 + long as u32 / 2) / long as u32).max(1) as u16
+        };
+        let (w, h) = match width >= height {
+            true => (max_dim, fit(height, width)),
+            false => (fit(width, height), max_dim),
+        };
+        self.scaled(w, h, filter)
+    }
+
+    // Multiplies the color channels of an ARGB32 image by its alpha.
+    pub fn premultiply_alpha(&mut self) -> Result<(), Error> {
+        self.require_format(&[TgaPixelFormat::ARGB32])?;
+        if self.premultiplied {
+            return Ok(());
+        }
+        for px in self.pixels_mut().chunks_exact_mut(4) {
+            let alpha = px[3] as u32;
+            for c in &mut px[..3] {
+                *c = ((*c as u32 * alpha + 127) / 255) as u8;
+            }
+        }
+        self.premultiplied = true;
+        Ok(())
+    }
+
+    // Swaps the first and third bytes of the RGB24 or ARGB32 pixels, so that they are
+    // stored in R, G, B(, A) order as most graphics APIs expect. The image must be
+    // swapped back with `to_bgra` before being saved or processed further: until then,
+    // the functions interpreting the channels return an error (or None, false).
+    pub fn to_rgba(&mut self) -> Result<(), Error> {
+        self.require_format(&[TgaPixelFormat::RGB24, TgaPixelFormat::ARGB32])?;
+        if self.rgba_order {
+            return Ok(());
+        }
+        self.map_pixels(|px| px.swap(0, 2))?;
+        self.rgba_order = true;
+        Ok(())
+    }
+
+    // Swaps the pixels back to the B, G, R(, A) order of the file after `to_rgba`.
+    pub fn to_bgra(&mut self) -> Result<(), Error> {
+        self.require_format(&[TgaPixelFormat::RGB24, TgaPixelFormat::ARGB32])?;
+        if !self.rgba_order {
+            return Ok(());
+        }
+        self.map_pixels(|px| px.swap(0, 2))?;
+        self.rgba_order = false;
+        Ok(())
+    }
+
+    // Divides the color channels of a premultiplied ARGB32 image by its alpha.
+    pub fn unpremultiply_alpha(&mut self) -> Result<(), Error> {
+        self.require_format(&[TgaPixelFormat::ARGB32])?;
+        if !self.premultiplied {
+            return Ok(());
+        }
+        for px in self.pixels_mut().chunks_exact_mut(4) {
+            let alpha = px[3] as u32;
+            for c in &mut px[..3] {
+                *c = match alpha {
+                    0 => 0,
+                    _ => ((*c as u32 * 255 + alpha / 2) / alpha).min(255) as u8,
+                };
+            }
+        }
+        self.premultiplied = false;
+        Ok(())
+    }
+
+    // Composites `top` over the image with straight (not premultiplied) alpha.
+    // Both images must be ARGB32 with equal dimensions.
+    pub fn compose_over(&mut self, top: &Tga) -> Result<(), Error> {
+        self.require_composable(top, false)?;
+        for (dst, src) in self.pixels_mut().chunks_exact_mut(4).zip(top.pixels().chunks_exact(4)) {
+            let src_a = src[3] as f32 / 255.0;
+            let dst_a = dst[3] as f32 / 255.0;
+            let out_a = src_a + dst_a * (1.0 - src_a);
+            for c in 0..3 {
+                dst[c] = match out_a > 0.0 {
+                    true => ((src[c] as f32 * src_a + dst[c] as f32 * dst_a * (1.0 - src_a)) / out_a).round() as u8,
+                    false => 0,
+                };
+            }
+            dst[3] = (out_a * 255.0).round() as u8;
+        }
+        Ok(())
+    }
+
+    // Composites `top` over the image with premultiplied alpha: dst = src + dst * (1 - src_a).
+    // Both images must be premultiplied ARGB32 with equal dimensions.
+    pub fn compose_over_premultiplied(&mut self, top: &Tga) -> Result<(), Error> {
+        self.require_composable(top, true)?;
+        for (dst, src) in self.pixels_mut().chunks_exact_mut(4).zip(top.pixels().chunks_exact(4)) {
+            let inverse_a = 255 - src[3] as u32;
+            for c in 0..4 {
+                dst[c] = (src[c] as u32 + (dst[c] as u32 * inverse_a + 127) / 255).min(255) as u8;
+            }
+        }
+        Ok(())
+    }
+
+    // Counts the scanlines identical to the previous one. A high count hints that
+    // RLE will compress well, or that the image is letterboxed.
+    pub fn duplicate_row_count(&self) -> usize {
+        let rows: Vec<&[u8]> = self.rows_iter().collect();
+        rows.windows(2).filter(|pair| pair[0] == pair[1]).count()
+    }
+
+    // Re-expresses a BW8 image as indexed: the indices are the gray levels and the
+    // palette is a 256 entries RGB24 gray ramp, entry i being gray i.
+    pub fn to_indexed_grayscale(&self) -> Result<IndexedTga, Error> {
+        self.require_format(&[TgaPixelFormat::BW8])?;
+        let (width, height) = self.dimensions();
+        Ok(IndexedTga {
+            width,
+            height,
+            palette_format: TgaPixelFormat::RGB24,
+            palette: (0..=255u8).flat_map(|i| [i, i, i]).collect(),
+            indices: self.pixels().to_vec(),
+        })
+    }
+
+    // Gets the perceptual luminance (0.299 R + 0.587 G + 0.114 B) of the pixel at (x, y).
+    // Grayscale pixels are returned as is, the high byte for BW16.
+    // Returns None if the coordinates are out of the image or the pixels were swapped
+    // by `to_rgba`.
+    pub fn luminance_at(&self, x: u16, y: u16) -> Option<u8> {
+        let [r, g, b, _] = self.rgba_at(x as usize, y as usize)?;
+        match self.info.pixel_format {
+            TgaPixelFormat::BW8 | TgaPixelFormat::BW16 => Some(r),
+            _ => Some(luma(r, g, b)),
+        }
+    }
+
+    // Makes an ARGB32 image keeping the colors, with each pixel's luminance as alpha.
+    // Turns a matte or an intensity map baked into a color image into transparency.
+    pub fn alpha_from_luminance(&self) -> Result<Tga, Error> {
+        self.require_file_order()?;
+        let pixel_size = self.pixel_size()? as usize;
+        let gray = matches!(self.info.pixel_format, TgaPixelFormat::BW8 | TgaPixelFormat::BW16);
+        let mut pixels = Vec::with_capacity(self.data.0.size() / pixel_size * 4);
+        for px in self.pixels().chunks_exact(pixel_size) {
+            let [r, g, b, _] = pixel_to_rgba(&self.info.pixel_format, px);
+            let alpha = if gray { r } else { luma(r, g, b) };
+            // Stored as BGRA.
+            pixels.extend_from_slice(&[b, g, r, alpha]);
+        }
+
+        let (width, height) = self.dimensions();
+        Tga::from_raw(width, height, TgaPixelFormat::ARGB32, &pixels)
+    }
+
+    // Copies the scanlines into `dst`, row `y` starting at `y * dst_stride`, as needed by
+    // texture uploads with padded rows. The padding bytes are left untouched.
+    // Returns Err(InvalidImageDimensions) if `dst_stride` is shorter than a scanline or
+    // `dst` can't hold every row.
+    pub fn copy_to_strided(&self, dst: &mut [u8], dst_stride: usize) -> Result<(), Error> {
+        let row_size = self.info.width as usize * self.pixel_size()? as usize;
+        let needed = dst_stride * (self.info.height as usize - 1) + row_size;
+        if dst_stride < row_size || dst.len() < needed {
+            return Err(Error::InvalidImageDimensions);
+        }
+
+        for (row, dst_row) in self.rows_iter().zip(dst.chunks_mut(dst_stride)) {
+            dst_row[..row_size].copy_from_slice(row);
+        }
+        Ok(())
+    }
+
+    // Describes the pixels for uploading them as a texture. The data isn't copied.
+    pub fn texture_descriptor(&self) -> Result<TextureDescriptor<'_>, Error> {
+        let format = match self.info.pixel_format {
+            TgaPixelFormat::BW8 => GpuFormat::R8,
+            TgaPixelFormat::BW16 => GpuFormat::R16,
+            TgaPixelFormat::RGB555 => GpuFormat::Bgr5A1,
+            TgaPixelFormat::RGB24 => GpuFormat::Bgr8,
+            TgaPixelFormat::ARGB32 => GpuFormat::Bgra8,
+        };
+        let (width, height) = self.dimensions();
+        Ok(TextureDescriptor {
+            width,
+            height,
+            format,
+            row_pitch: width as usize * self.pixel_size()? as usize,
+            data: self.pixels(),
+        })
+    }
+
+    // Calls `f` on the stored bytes of every pixel in row-major order, e.g. to tint,
+    // invert or swap channels in place.
+    pub fn map_pixels<F: FnMut(&mut [u8])>(&mut self, f: F) -> Result<(), Error> {
+        let pixel_size = self.pixel_size()? as usize;
+        self.pixels_mut().chunks_exact_mut(pixel_size).for_each(f);
+        Ok(())
+    }
+
+    // Calls `f` on the stored bytes of every pixel, spreading the scanlines over the
+    // rayon thread pool.
+    #[cfg(feature = "rayon")]
+    pub fn par_map_pixels<F: Fn(&mut [u8]) + Sync>(&mut self, f: F) -> Result<(), Error> {
+        use rayon::prelude::*;
+
+        let pixel_size = self.pixel_size()? as usize;
+        let row_size = self.info.width as usize * pixel_size;
+        self.pixels_mut()
+            .par_chunks_mut(row_size)
+            .for_each(|row| row.chunks_exact_mut(pixel_size).for_each(&f));
+
+        Ok(())
+    }
+
+    // Converts the pixels to another format, updating `info` and `header`.
+    // Alpha is 255 (or the RGB555 attribute bit set) when the source has none and is
+    // dropped when the target has none. Color to grayscale uses the luma of `luminance_at`,
+    // BW16 keeps its full precision from and to grayscale.
+    // Returns Err(UnsupportedPixelFormat) if the pixels were swapped by `to_rgba`.
+    pub fn convert(&mut self, target: TgaPixelFormat) -> Result<(), Error> {
+        if self.info.pixel_format == target {
+            return Ok(());
+        }
+        self.require_file_order()?;
+
+        let pixel_size = self.pixel_size()? as usize;
+        let source = self.info.pixel_format;
+        // Without attribute bits the alpha byte (or bit) is garbage, read it as opaque.
+        let has_alpha = self.info.has_alpha();
+        let mut pixels = Vec::with_capacity(self.pixels().len() / pixel_size * 4);
+        for px in self.pixels().chunks_exact(pixel_size) {
+            let [r, g, b, mut a] = pixel_to_rgba(&source, px);
+            if !has_alpha {
+                a = 255;
+            }
+            let gray16 = match source {
+                TgaPixelFormat::BW16 => u16::from_le_bytes([px[0], px[1]]),
+                TgaPixelFormat::BW8 => r as u16 * 257,
+                _ => luma(r, g, b) as u16 * 257,
+            };
+            match target {
+                TgaPixelFormat::BW8 => pixels.push((gray16 >> 8) as u8),
+                TgaPixelFormat::BW16 => pixels.extend_from_slice(&gray16.to_le_bytes()),
+                TgaPixelFormat::RGB555 | TgaPixelFormat::RGB24 | TgaPixelFormat::ARGB32 => {
+                    let mut px = [0u8; 4];
+                    let size = rgba_to_pixel(&target, [r, g, b, a], &mut px);
+                    pixels.extend_from_slice(&px[..size]);
+                },
+            }
+        }
+
+        let (width, height) = self.dimensions();
+        self.set_format(target);
+        self.replace_data(width, height, &pixels)?;
+        if target != TgaPixelFormat::ARGB32 {
+            self.premultiplied = false;
+        }
+        Ok(())
+    }
+
+    // Expands RGB555 pixels to RGB24, each 5 bits channel scaled to the full 8 bits range.
+    // The high bit of the word is the attribute (alpha) bit and is dropped.
+    pub fn unpack_rgb555_to_rgb24(&mut self) -> Result<(), Error> {
+        self.require_format(&[TgaPixelFormat::RGB555])?;
+        self.convert(TgaPixelFormat::RGB24)
+    }
+
+    // Counts the pixels for which `pred` returns true. `pred` gets the stored bytes of
+    // each pixel, e.g. B, G, R, A for ARGB32.
+    pub fn count_matching<F: Fn(&[u8]) -> bool>(&self, pred: F) -> usize {
+        let Ok(pixel_size) = self.pixel_size() else {
+            return 0;
+        };
+        self.pixels().chunks_exact(pixel_size as usize).filter(|px| pred(px)).count()
+    }
+
+    // Replaces the 4-connected region of pixels sharing the color of (`x`, `y`) by
+    // `color`, given as stored bytes of the image format. Returns the filled pixel count.
+    // Returns Err(InvalidImageDimensions) if the seed is outside the image and
+    // Err(UnsupportedPixelFormat) if `color` doesn't have the pixel size.
+    pub fn flood_fill(&mut self, x: u16, y: u16, color: &[u8]) -> Result<usize, Error> {
+        let pixel_size = self.pixel_size()? as usize;
+        if color.len() != pixel_size {
+            return Err(Error::UnsupportedPixelFormat);
+        }
+        let (width, height) = self.dimensions();
+        if x >= width || y >= height {
+            return Err(Error::InvalidImageDimensions);
+        }
+
+        let (width, height) = (width as usize, height as usize);
+        let data = self.pixels_mut();
+        let seed = y as usize * width + x as usize;
+        let target = data[seed * pixel_size..(seed + 1) * pixel_size].to_vec();
+        let mut visited = vec![false; width * height];
+        // An explicit queue, large regions would overflow the stack with recursion.
+        let mut queue = VecDeque::from([seed]);
+        visited[seed] = true;
+        let mut filled = 0;
+
+        while let Some(i) = queue.pop_front() {
+            data[i * pixel_size..(i + 1) * pixel_size].copy_from_slice(color);
+            filled += 1;
+
+            let (px, py) = (i % width, i / width);
+            let neighbors = [
+                (px > 0).then(|| i - 1),
+                (px + 1 < width).then(|| i + 1),
+                (py > 0).then(|| i - width),
+                (py + 1 < height).then(|| i + width),
+            ];
+            for n in neighbors.into_iter().flatten() {
+                if !visited[n] && data[n * pixel_size..(n + 1) * pixel_size] == target[..] {
+                    visited[n] = true;
+                    queue.push_back(n);
+                }
+            }
+        }
+
+        Ok(filled)
+    }
+
+    // Blurs the image with a box filter of the given radius, alpha is left untouched.
+    // Runs a horizontal then a vertical pass with a running sum, so the cost doesn't
+    // depend on the radius. Only formats with 8 bits channels are supported.
+    pub fn box_blur(&mut self, radius: u16) -> Result<(), Error> {
+        self.require_format(&[TgaPixelFormat::BW8, TgaPixelFormat::RGB24, TgaPixelFormat::ARGB32])?;
+        let channels = if self.info.pixel_format == TgaPixelFormat::BW8 { 1 } else { 3 };
+        if radius == 0 {
+            return Ok(());
+        }
+
+        let pixel_size = self.pixel_size()? as usize;
+        let width = self.info.width as usize;
+        let height = self.info.height as usize;
+        let row_size = width * pixel_size;
+        let data = self.pixels_mut();
+
+        for c in 0..channels {
+            for y in 0..height {
+                box_blur_line(data, y * row_size + c, pixel_size, width, radius as usize);
+            }
+            for x in 0..width {
+                box_blur_line(data, x * pixel_size + c, row_size, height, radius as usize);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Converts a heightmap (the luminance of the image) to a tangent-space normal map.
+    // Slopes are central differences of the neighboring heights scaled by `strength`,
+    // rows are taken top to bottom with green pointing up. Returns a RGB24 image where
+    // each normal component is mapped from [-1, 1] to [0, 255].
+    pub fn heightmap_to_normal(&self, strength: f32) -> Result<Tga, Error> {
+        let width = self.info.width as usize;
+        let height = self.info.height as usize;
+        let heights = self.luma_plane()?;
+        let mut normals = vec![0u8; width * height * 3];
+
+        let at = |x: isize, y: isize| -> f32 {
+            let sx = x.clamp(0, width as isize - 1) as usize;
+            let sy = y.clamp(0, height as isize - 1) as usize;
+            heights[sy * width + sx] as f32 / 255.0
+        };
+        let encode = |n: f32| ((n * 0.5 + 0.5) * 255.0).round() as u8;
+
+        for y in 0..height {
+            for x in 0..width {
+                let (ix, iy) = (x as isize, y as isize);
+                let dx = (at(ix + 1, iy) - at(ix - 1, iy)) * 0.5 * strength;
+                let dy = (at(ix, iy - 1) - at(ix, iy + 1)) * 0.5 * strength;
+                let len = (dx * dx + dy * dy + 1.0).sqrt();
+                let offset = (y * width + x) * 3;
+                // Stored as BGR.
+                normals[offset] = encode(1.0 / len);
+                normals[offset + 1] = encode(-dy / len);
+                normals[offset + 2] = encode(-dx / len);
+            }
+        }
+
+        Tga::from_raw(self.info.width, self.info.height, TgaPixelFormat::RGB24, &normals)
+    }
+
+    // Splits the image into one BW8 image per channel, in R, G, B, A order.
+    // Grayscale images yield a single channel and RGB555 is expanded to 8 bits channels.
+    // Returns Err(UnsupportedPixelFormat) if the pixels were swapped by `to_rgba`.
+    pub fn split_channels(&self) -> Result<Vec<Tga>, Error> {
+        self.require_file_order()?;
+        let pixel_size = self.pixel_size()? as usize;
+        let count = match self.info.pixel_format {
+            TgaPixelFormat::BW8 | TgaPixelFormat::BW16 => 1,
+            TgaPixelFormat::RGB555 | TgaPixelFormat::RGB24 => 3,
+            TgaPixelFormat::ARGB32 => 4,
+        };
+
+        let mut planes = vec![Vec::with_capacity(self.data.0.size() / pixel_size); count];
+        for px in self.pixels().chunks_exact(pixel_size) {
+            let rgba = pixel_to_rgba(&self.info.pixel_format, px);
+            for (plane, value) in planes.iter_mut().zip(rgba) {
+                plane.push(value);
+            }
+        }
+
+        planes
+            .iter()
+            .map(|plane| Tga::from_raw(self.info.width, self.info.height, TgaPixelFormat::BW8, plane))
+            .collect()
+    }
+
+    // Merges BW8 images of equal dimensions given in R, G, B, A order.
+    // Three channels make a RGB24 image, four channels make an ARGB32 image.
+    pub fn merge_channels(channels: &[&Tga]) -> Result<Tga, Error> {
+        let format = match channels.len() {
+            3 => TgaPixelFormat::RGB24,
+            4 => TgaPixelFormat::ARGB32,
+            _ => return Err(Error::UnsupportedPixelFormat),
+        };
+        for channel in channels {
+            channel.require_format(&[TgaPixelFormat::BW8])?;
+            channels[0].ensure_same_dimensions(channel)?;
+        }
+
+        let planes: Vec<&[u8]> = channels.iter().map(|channel| channel.pixels()).collect();
+        let mut pixels = Vec::with_capacity(planes[0].len() * channels.len());
+        for i in 0..planes[0].len() {
+            // Stored as BGR(A).
+            pixels.extend_from_slice(&[planes[2][i], planes[1][i], planes[0][i]]);
+            if let Some(alpha) = planes.get(3) {
+                pixels.push(alpha[i]);
+            }
+        }
+
+        let (width, height) = channels[0].dimensions();
+        Tga::from_raw(width, height, format, &pixels)
+    }
+
+    // Packs BW8 images of equal dimensions into the channels of one image, as is done
+    // for material maps. Returns an ARGB32 image if `a` is given, otherwise a RGB24 image.
+    pub fn pack_channels(r: &Tga, g: &Tga, b: &Tga, a: Option<&Tga>) -> Result<Tga, Error> {
+        match a {
+            Some(a) => Tga::merge_channels(&[r, g, b, a]),
+            None => Tga::merge_channels(&[r, g, b]),
+        }
+    }
+
+    // Computes the 64 bits difference hash of the image for perceptual comparison.
+    // The luminance is averaged down to 9x8 blocks, each bit tells whether a block
+    // is brighter than its right neighbor. Compare hashes with `hamming_distance`.
+    pub fn dhash(&self) -> u64 {
+        let Ok(gray) = self.luma_plane() else {
+            return 0;
+        };
+        let width = self.info.width as usize;
+        let height = self.info.height as usize;
+
+        // Averages the luminance of the block at (bx, by) on a 9x8 grid.
+        let block = |bx: usize, by: usize| -> u32 {
+            let (x0, y0) = (bx * width / 9, by * height / 8);
+            let x1 = ((bx + 1) * width / 9).max(x0 + 1).min(width);
+            let y1 = ((by + 1) * height / 8).max(y0 + 1).min(height);
+            let x0 = x0.min(x1 - 1);
+            let y0 = y0.min(y1 - 1);
+            let sum: u32 = (y0..y1).flat_map(|y| &gray[y * width + x0..y * width + x1]).map(|v| *v as u32).sum();
+            sum / ((x1 - x0) * (y1 - y0)) as u32
+        };
+
+        let mut hash = 0u64;
+        for by in 0..8 {
+            let row: Vec<u32> = (0..9).map(|bx| block(bx, by)).collect();
+            for bx in 0..8 {
+                hash = (hash << 1) | (row[bx] > row[bx + 1]) as u64;
+            }
+        }
+        hash
+    }
+
+    // Trims the borders made of `background` (or fully transparent pixels for ARGB32)
+    // and surrounds what's left with `padding` pixels of `background`, as atlas packers want.
+    // Returns the new image and the rect of the kept content in the original image.
+    pub fn normalize_for_atlas(&self, padding: u16, background: &[u8]) -> Result<(Tga, Rect), Error> {
+        let pixel_size = self.pixel_size()? as usize;
+        if background.len() != pixel_size {
+            return Err(Error::UnsupportedPixelFormat);
+        }
+
+        let width = self.info.width as usize;
+        let is_background = |px: &[u8]| {
+            px == background || (self.info.pixel_format == TgaPixelFormat::ARGB32 && px[3] == 0)
+        };
+        let (mut x0, mut y0, mut x1, mut y1) = (usize::MAX, usize::MAX, 0, 0);
+        for (i, px) in self.pixels().chunks_exact(pixel_size).enumerate() {
+            if !is_background(px) {
+                let (x, y) = (i % width, i / width);
+                x0 = x0.min(x);
+                y0 = y0.min(y);
+                x1 = x1.max(x + 1);
+                y1 = y1.max(y + 1);
+            }
+        }
+        if x0 == usize::MAX {
+            return Err(Error::NoData);
+        }
+
+        let rect = Rect { x: x0 as u16, y: y0 as u16, width: (x1 - x0) as u16, height: (y1 - y0) as u16 };
+        let out_width = rect.width as usize + 2 * padding as usize;
+        let out_height = rect.height as usize + 2 * padding as usize;
+        if !check_dimensions(out_width as u32, out_height as u32) {
+            return Err(Error::InvalidImageDimensions);
+        }
+
+        let mut pixels = background.repeat(out_width * out_height);
+        let row_size = rect.width as usize * pixel_size;
+        for y in 0..rect.height as usize {
+            let src = ((y0 + y) * width + x0) * pixel_size;
+            let dst = ((padding as usize + y) * out_width + padding as usize) * pixel_size;
+            pixels[dst..dst + row_size].copy_from_slice(&self.pixels()[src..src + row_size]);
+        }
+
+        let tga = Tga::from_raw(out_width as u16, out_height as u16, self.info.pixel_format, &pixels)?;
+        Ok((tga, rect))
+    }
+
+    // Keeps only the `w` x `h` pixels at (`x`, `y`), e.g. to slice sprite sheets.
+    // Returns Err(InvalidImageDimensions) if the region is empty or not inside the image.
+    pub fn crop(&mut self, x: u16, y: u16, w: u16, h: u16) -> Result<(), Error> {
+        let cropped = self.copy_rect(Rect { x, y, width: w, height: h })?;
+        self.data = cropped.data;
+        self.set_dimensions(w, h);
+        Ok(())
+    }
+
+    // Crops the image to the largest region with a `w_ratio`:`h_ratio` aspect ratio,
+    // placed according to `anchor`. The image isn't scaled.
+    // Returns Err(InvalidImageDimensions) if a ratio is zero.
+    pub fn crop_to_aspect(&self, w_ratio: u16, h_ratio: u16, anchor: Anchor) -> Result<Tga, Error> {
+        if w_ratio == 0 || h_ratio == 0 {
+            return Err(Error::InvalidImageDimensions);
+        }
+
+        let (width, height) = self.dimensions();
+        let (w, h) = if width as u32 * h_ratio as u32 >= height as u32 * w_ratio as u32 {
+            ((height as u32 * w_ratio as u32 / h_ratio as u32).max(1) as u16, height)
+        } else {
+            (width, (width as u32 * h_ratio as u32 / w_ratio as u32).max(1) as u16)
+        };
+        let (x, y) = anchor.offset((width - w).into(), (height - h).into());
+
+        self.copy_rect(Rect { x: x as u16, y: y as u16, width: w, height: h })
+    }
+
+    // Darkens the pixels by `1 - strength * d^2`, d being the distance to the center
+    // normalized so that the corners are at 1. Alpha is left untouched.
+    // Only formats with 8 bits channels are supported.
+    pub fn vignette(&mut self, strength: f32) -> Result<(), Error> {
+        self.require_format(&[TgaPixelFormat::BW8, TgaPixelFormat::RGB24, TgaPixelFormat::ARGB32])?;
+        let channels = if self.info.pixel_format == TgaPixelFormat::BW8 { 1 } else { 3 };
+        let pixel_size = self.pixel_size()? as usize;
+        let width = self.info.width as usize;
+        let (cx, cy) = (self.info.width as f32 / 2.0, self.info.height as f32 / 2.0);
+        let max_distance2 = cx * cx + cy * cy;
+
+        for (i, px) in self.pixels_mut().chunks_exact_mut(pixel_size).enumerate() {
+            let dx = (i % width) as f32 + 0.5 - cx;
+            let dy = (i / width) as f32 + 0.5 - cy;
+            let factor = (1.0 - strength * (dx * dx + dy * dy) / max_distance2).clamp(0.0, 1.0);
+            for c in &mut px[..channels] {
+                *c = (*c as f32 * factor).round() as u8;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Removes the tint a chroma key color (`key` in R, G, B order) leaves on the pixels.
+    // The part of each pixel's chroma pointing towards the key's chroma is reduced by
+    // `amount` (0 to 1), which keeps the pixel brightness. Gray keys change nothing.
+    // Only RGB24 and ARGB32 are supported.
+    pub fn suppress_spill(&mut self, key: [u8; 3], amount: f32) -> Result<(), Error> {
+        self.require_format(&[TgaPixelFormat::RGB24, TgaPixelFormat::ARGB32])?;
+        let pixel_size = self.pixel_size()? as usize;
+        let amount = amount.clamp(0.0, 1.0);
+        // Stored as BGR.
+        let key = [key[2] as f32, key[1] as f32, key[0] as f32];
+        let key_mean = key.iter().sum::<f32>() / 3.0;
+        let direction = key.map(|c| c - key_mean);
+        let direction_len2: f32 = direction.iter().map(|d| d * d).sum();
+        if direction_len2 == 0.0 {
+            return Ok(());
+        }
+
+        for px in self.pixels_mut().chunks_exact_mut(pixel_size) {
+            let mean = (px[0] as f32 + px[1] as f32 + px[2] as f32) / 3.0;
+            let spill: f32 = px[..3].iter().zip(direction).map(|(c, d)| (*c as f32 - mean) * d).sum::<f32>() / direction_len2;
+            if spill > 0.0 {
+                for (c, d) in px[..3].iter_mut().zip(direction) {
+                    *c = (*c as f32 - amount * spill * d).round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Reduces the image to at most `max_colors` (1 to 256) colors with the median cut
+    // algorithm: the box of colors with the widest channel range is split at its median
+    // until there are enough boxes, each box giving the average of its colors.
+    // The palette is ARGB32 for ARGB32 images and RGB24 otherwise.
+    // Returns Err(UnsupportedPixelFormat) if the pixels were swapped by `to_rgba`.
+    pub fn quantize(&self, max_colors: u16) -> Result<IndexedTga, Error> {
+        if max_colors == 0 || max_colors > 256 {
+            return Err(Error::InvalidPaletteSize);
+        }
+        self.require_file_order()?;
+
+        let pixel_size = self.pixel_size()? as usize;
+        let colors: Vec<[u8; 4]> = self.pixels()
+            .chunks_exact(pixel_size)
+            .map(|px| pixel_to_rgba(&self.info.pixel_format, px))
+            .collect();
+        let channels = if self.info.pixel_format == TgaPixelFormat::ARGB32 { 4 } else { 3 };
+
+        // Boxes hold the indices of their pixels.
+        let mut boxes: Vec<Vec<usize>> = vec![(0..colors.len()).collect()];
+        while boxes.len() < max_colors as usize {
+            let range = |pixels: &[usize], c: usize| {
+                let (min, max) = pixels.iter().fold((255, 0), |(min, max), i| (colors[*i][c].min(min), colors[*i][c].max(max)));
+                max - min
+            };
+            let widest = (0..boxes.len())
+                .flat_map(|b| (0..channels).map(move |c| (b, c)))
+                .map(|(b, c)| (range(&boxes[b], c), b, c))
+                .max();
+            let Some((width, b, c)) = widest else { break };
+            if width == 0 {
+                break;
+            }
+
+            let mut pixels = boxes.swap_remove(b);
+            pixels.sort_unstable_by_key(|i| colors[*i][c]);
+            let upper = pixels.split_off(pixels.len() / 2);
+            boxes.push(pixels);
+            boxes.push(upper);
+        }
+
+        let palette_format = if channels == 4 { TgaPixelFormat::ARGB32 } else { TgaPixelFormat::RGB24 };
+        let mut palette = Vec::with_capacity(boxes.len() * channels);
+        let mut indices = vec![0u8; colors.len()];
+        for (index, pixels) in boxes.iter().enumerate() {
+            let mut sum = [0usize; 4];
+            for i in pixels {
+                for c in 0..4 {
+                    sum[c] += colors[*i][c] as usize;
+                }
+                indices[*i] = index as u8;
+            }
+            let average = sum.map(|s| ((s + pixels.len() / 2) / pixels.len()) as u8);
+            // Stored as BGR(A).
+            palette.extend_from_slice(&[average[2], average[1], average[0], average[3]][..channels]);
+        }
+
+        Ok(IndexedTga {
+            width: self.info.width,
+            height: self.info.height,
+            palette_format,
+            palette,
+            indices,
+        })
+    }
+
+    // Reduces an RGB24 or ARGB32 image to at most `max_colors` colors as `quantize` does,
+    // then stores it color mapped: the palette goes to `map`, `data` holds an 8 bits index
+    // per pixel and the header describes an uncompressed color mapped image (type 1),
+    // which `save` and `save_colormapped` write. Operations on the pixel values return
+    // Err(UnsupportedPixelFormat) afterwards.
+    // Returns Err(InvalidPaletteSize) if `max_colors` is 0 or more than 256.
+    pub fn to_colormapped(&mut self, max_colors: u16) -> Result<(), Error> {
+        self.require_format(&[TgaPixelFormat::RGB24, TgaPixelFormat::ARGB32])?;
+        let indexed = self.quantize(max_colors)?;
+        let map = ColorMap::new(0, indexed.bytes_per_entry() as u8, &indexed.palette)?;
+        let (width, height) = self.dimensions();
+        self.replace_data(width, height, &indexed.indices)?;
+        self.header.map_type = 1;
+        self.header.image_type = TgaImageType::ColorMapped as u8;
+        self.header.map_first_entry = map.first_index;
+        self.header.map_length = map.entry_count;
+        self.header.map_entry_size = map.bytes_per_entry * 8;
+        self.header.pixel_depth = 8;
+        self.map = Some(map);
+        self.indexed = true;
+        Ok(())
+    }
+
+    // Checks whether two images of equal dimensions have all their R, G, B, A channels
+    // within `tolerance` of each other. The formats may differ.
+    // Returns false if either image was swapped by `to_rgba`.
+    pub fn approx_eq(&self, other: &Tga, tolerance: u8) -> bool {
+        if self.info.width != other.info.width || self.info.height != other.info.height {
+            return false;
+        }
+        if self.require_file_order().is_err() || other.require_file_order().is_err() {
+            return false;
+        }
+        let (Ok(pixel_size), Ok(other_pixel_size)) = (self.pixel_size(), other.pixel_size()) else {
+            return false;
+        };
+
+        self.pixels()
+            .chunks_exact(pixel_size as usize)
+            .zip(other.pixels().chunks_exact(other_pixel_size as usize))
+            .all(|(a, b)| {
+                let (a, b) = (pixel_to_rgba(&self.info.pixel_format, a), pixel_to_rgba(&other.info.pixel_format, b));
+                a.iter().zip(b).all(|(x, y)| x.abs_diff(y) <= tolerance)
+            })
+    }
+
+    // Builds an image from tightly packed pixel data in the given format, stored as
+    // `pixels` lays it out, e.g. to render into a buffer and then `save` it.
+    // The header is filled in so that it describes an uncompressed image.
+    // Returns Err(InvalidImageDimensions) if `pixels` doesn't hold exactly the image.
+    pub fn from_raw(width: u16, height: u16, format: TgaPixelFormat, pixels: &[u8]) -> Result<Self, Error> {
+        if !check_dimensions(width.into(), height.into()) {
+            return Err(Error::InvalidImageDimensions);
+        }
+
+        let header = TgaHeader::for_format(width, height, &format);
+        let size = image_size(width, height, header.get_pixel_size()?)?;
+        if pixels.len() != size {
+            return Err(Error::InvalidImageDimensions);
+        }
+
+        let data = LayPtr::alloc(size, false)?;
+        unsafe {
+            ptr::copy_nonoverlapping(pixels.as_ptr(), data.1, size);
+        }
+
+        Ok(Self {
+            header,
+            info: TgaInfo::for_format(width, height, format),
+            data,
+            map: None,
+            image_id: Vec::new(),
+            premultiplied: false,
+            rgba_order: false,
+            indexed: false,
+            extension: None,
+            developer_fields: Vec::new(),
+        })
+    }
+
+    // Checks that `header` still describes `info` and the pixel data: same dimensions,
+    // same pixel format and a data size matching both.
+    // Returns Err(IllegalHeader) if they drifted apart.
+    pub fn validate_against_header(&self) -> Result<(), Error> {
+        let (width, height) = self.dimensions();
+        let size = image_size(width, height, self.stored_pixel_size()?)?;
+        if (self.header.image_width, self.header.image_height) != (width, height)
+            || self.header.get_pixel_format()? != self.info.pixel_format
+            || size != self.data.0.size()
+        {
+            return Err(Error::IllegalHeader);
+        }
+        Ok(())
+    }
+
+    // Rotates the image by 90 degrees clockwise, swapping its width and height.
+    pub fn rotate90(&mut self) -> Result<(), Error> {
+        let pixel_size = self.pixel_size()? as usize;
+        let (width, height) = self.dimensions();
+        let (width, height) = (width as usize, height as usize);
+        let src = self.pixels();
+        let mut rotated = Vec::with_capacity(src.len());
+        for y in 0..width {
+            for x in 0..height {
+                let offset = ((height - 1 - x) * width + y) * pixel_size;
+                rotated.extend_from_slice(&src[offset..offset + pixel_size]);
+            }
+        }
+
+        self.replace_data(height as u16, width as u16, &rotated)
+    }
+
+    // Rotates the image by 180 degrees.
+    pub fn rotate180(&mut self) -> Result<(), Error> {
+        let pixel_size = self.pixel_size()? as usize;
+        let data = self.pixels_mut();
+        // Reversing the bytes reverses the pixel order, then each pixel gets its bytes back.
+        data.reverse();
+        data.chunks_exact_mut(pixel_size).for_each(|px| px.reverse());
+        Ok(())
+    }
+
+    // Rotates the image by 270 degrees clockwise (90 counterclockwise), swapping its
+    // width and height.
+    pub fn rotate270(&mut self) -> Result<(), Error> {
+        let pixel_size = self.pixel_size()? as usize;
+        let (width, height) = self.dimensions();
+        let (width, height) = (width as usize, height as usize);
+        let src = self.pixels();
+        let mut rotated = Vec::with_capacity(src.len());
+        for y in 0..width {
+            for x in 0..height {
+                let offset = (x * width + width - 1 - y) * pixel_size;
+                rotated.extend_from_slice(&src[offset..offset + pixel_size]);
+            }
+        }
+
+        self.replace_data(height as u16, width as u16, &rotated)
+    }
+
+    // Scales the image to `out_w` x `out_h` pixels into a new image of the same format.
+    // Returns Err(InvalidImageDimensions) if the new dimensions aren't valid.
+    fn scaled(&self, out_w: u16, out_h: u16, filter: ScaleFilter) -> Result<Tga, Error> {
+        if !check_dimensions(out_w.into(), out_h.into()) {
+            return Err(Error::InvalidImageDimensions);
+        }
+
+        let pixel_size = self.pixel_size()? as usize;
+        let (width, height) = self.dimensions();
+        let (width, height) = (width as usize, height as usize);
+        let (out_w, out_h) = (out_w as usize, out_h as usize);
+        let src = self.pixels();
+        let px = |x: usize, y: usize| &src[(y * width + x) * pixel_size..][..pixel_size];
+        let bilinear = filter == ScaleFilter::Bilinear
+            && !matches!(self.info.pixel_format, TgaPixelFormat::RGB555 | TgaPixelFormat::BW16);
+
+        let mut pixels = Vec::with_capacity(image_size(out_w as u16, out_h as u16, pixel_size as u32)?);
+        for y in 0..out_h {
+            for x in 0..out_w {
+                if !bilinear {
+                    pixels.extend_from_slice(px(x * width / out_w, y * height / out_h));
+                    continue;
+                }
+
+                // Pixel centers are aligned: destination x + 0.5 maps to source (x + 0.5) * scale.
+                let fx = ((x as f32 + 0.5) * width as f32 / out_w as f32 - 0.5).clamp(0.0, (width - 1) as f32);
+                let fy = ((y as f32 + 0.5) * height as f32 / out_h as f32 - 0.5).clamp(0.0, (height - 1) as f32);
+                let (x0, y0) = (fx as usize, fy as usize);
+                let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+                let (tx, ty) = (fx - x0 as f32, fy - y0 as f32);
+                for c in 0..pixel_size {
+                    let top = px(x0, y0)[c] as f32 * (1.0 - tx) + px(x1, y0)[c] as f32 * tx;
+                    let bottom = px(x0, y1)[c] as f32 * (1.0 - tx) + px(x1, y1)[c] as f32 * tx;
+                    pixels.push((top * (1.0 - ty) + bottom * ty).round() as u8);
+                }
+            }
+        }
+
+        Tga::from_raw(out_w as u16, out_h as u16, self.info.pixel_format, &pixels)
+    }
+
+    // Copies the pixels inside `rect` into a new image of the same format.
+    // Returns Err(InvalidImageDimensions) if `rect` is empty or not inside the image.
+    fn copy_rect(&self, rect: Rect) -> Result<Tga, Error> {
+        let (width, height) = self.dimensions();
+        if rect.width == 0
+            || rect.height == 0
+            || rect.x as u32 + rect.width as u32 > width as u32
+            || rect.y as u32 + rect.height as u32 > height as u32
+        {
+            return Err(Error::InvalidImageDimensions);
+        }
+
+        let pixel_size = self.pixel_size()? as usize;
+        let row_size = rect.width as usize * pixel_size;
+        let mut pixels = Vec::with_capacity(row_size * rect.height as usize);
+        for row in self.rows_iter().skip(rect.y as usize).take(rect.height as usize) {
+            let start = rect.x as usize * pixel_size;
+            pixels.extend_from_slice(&row[start..start + row_size]);
+        }
+
+        Tga::from_raw(rect.width, rect.height, self.info.pixel_format, &pixels)
+    }
+
+    // Replaces the pixel data by `pixels` of the same format and the new dimensions.
+    fn replace_data(&mut self, width: u16, height: u16, pixels: &[u8]) -> Result<(), Error> {
+        let data = LayPtr::alloc(pixels.len(), false)?;
+        unsafe {
+            ptr::copy_nonoverlapping(pixels.as_ptr(), data.1, pixels.len());
+        }
+        self.data = data;
+        self.set_dimensions(width, height);
+        Ok(())
+    }
+
+    // Gets the image width and height. `info` is the single source of the dimensions,
+    // `header` only mirrors it through `set_dimensions`.
+    #[inline]
+    pub fn dimensions(&self) -> (u16, u16) {
+        (self.info.width, self.info.height)
+    }
+
+    // Gets the meaningful bits per pixel: 15 for RGB555, whose words have an attribute
+    // bit, otherwise the storage size `get_pixel_size` in bits. The header keeps the
+    // storage size as pixel depth. Indexed images store 8 bits indices.
+    pub fn bits_per_pixel(&self) -> u32 {
+        match self.info.pixel_format {
+            _ if self.indexed => 8,
+            TgaPixelFormat::BW8 => 8,
+            TgaPixelFormat::RGB555 => 15,
+            TgaPixelFormat::BW16 => 16,
+            TgaPixelFormat::RGB24 => 24,
+            TgaPixelFormat::ARGB32 => 32,
+        }
+    }
+
+    // Gets whether the image is as wide as it is high, e.g. for cubemap faces.
+    #[inline]
+    pub fn is_square(&self) -> bool {
+        self.info.width == self.info.height
+    }
+
+    // Gets the width divided by the height.
+    #[inline]
+    pub fn aspect_ratio(&self) -> f32 {
+        self.info.width as f32 / self.info.height as f32
+    }
+
+    // Stores new dimensions in both `info` and `header`, so that they never drift apart.
+    // Every transform changing the image size must go through it.
+    fn set_dimensions(&mut self, width: u16, height: u16) {
+        self.info.width = width;
+        self.info.height = height;
+        self.header.image_width = width;
+        self.header.image_height = height;
+    }
+
+    // Declares the data as uncompressed pixels of the given format, in `info` and `header`.
+    fn set_format(&mut self, format: TgaPixelFormat) {
+        let header = TgaHeader::for_format(self.info.width, self.info.height, &format);
+        self.header.image_type = header.image_type;
+        self.header.pixel_depth = header.pixel_depth;
+        self.header.map_type = 0;
+        self.header.map_first_entry = 0;
+        self.header.map_length = 0;
+        self.header.map_entry_size = 0;
+        self.map = None;
+        self.header.image_descripter = (self.header.image_descripter & 0xF0) | (header.image_descripter & 0x0F);
+        self.info.pixel_format = format;
+        self.info.attribute_bits = header.image_descripter & 0x0F;
+    }
+
+    // Checks that the pixels are in the byte order of the file, i.e. not swapped by `to_rgba`.
+    #[inline]
+    pub(crate) fn require_file_order(&self) -> Result<(), Error> {
+        match self.rgba_order {
+            true => Err(Error::UnsupportedPixelFormat),
+            false => Ok(()),
+        }
+    }
+
+    // Gets the bytes per pixel, for operations working on the pixel values.
+    // Returns Err(UnsupportedPixelFormat) if `data` holds color map indices.
+    #[inline]
+    pub(crate) fn pixel_size(&self) -> Result<u32, Error> {
+        match self.indexed {
+            true => Err(Error::UnsupportedPixelFormat),
+            false => self.header.get_pixel_size(),
+        }
+    }
+
+    // Gets the bytes per element of `data`: 1 for indexed images, the pixel size otherwise.
+    #[inline]
+    fn stored_pixel_size(&self) -> Result<u32, Error> {
+        match self.indexed {
+            true => Ok(1),
+            false => self.header.get_pixel_size(),
+        }
+    }
+
+    // Gets the color map entries written before the pixels: those of `map` for indexed
+    // images, none otherwise as the pixels are saved expanded.
+    fn stored_color_map(&self) -> &[u8] {
+        match (self.indexed, self.map.as_ref()) {
+            (true, Some(map)) => map.entries(),
+            _ => &[],
+        }
+    }
+
+    // Checks that `top` can be composited over the image.
+    fn require_composable(&self, top: &Tga, premultiplied: bool) -> Result<(), Error> {
+        self.require_format(&[TgaPixelFormat::ARGB32])?;
+        top.require_format(&[TgaPixelFormat::ARGB32])?;
+        self.require_file_order()?;
+        top.require_file_order()?;
+        if self.premultiplied != premultiplied || top.premultiplied != premultiplied {
+            return Err(Error::UnsupportedPixelFormat);
+        }
+        self.ensure_same_dimensions(top)
+    }
+
+    // Consumes the image and returns its pixels, laid out as `pixels`.
+    // The data is copied: `LayPtr` allocates with its own `Layout`, which a `Vec` can't
+    // take over.
+    pub fn into_vec(self) -> Vec<u8> {
+        self.pixels().to_vec()
+    }
+
+    // Views the decoded pixels as a slice: tightly packed scanlines, origin at the upper
+    // left corner, in the stored byte order of the pixel format (BGR(A) for true color).
+    #[inline]
+    pub fn pixels(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.data.1, self.data.0.size()) }
+    }
+
+    // Views the decoded pixels as a mutable slice, laid out as `pixels`.
+    #[inline]
+    pub fn pixels_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.data.1, self.data.0.size()) }
+    }
+
+    // Gets the pixel at (x, y) in R, G, B, A order.
+    // Returns None if the coordinates are out of the image or the pixels were swapped
+    // by `to_rgba`.
+    #[inline]
+    fn rgba_at(&self, x: usize, y: usize) -> Option<[u8; 4]> {
+        if x >= self.info.width as usize || y >= self.info.height as usize {
+            return None;
+        }
+        self.require_file_order().ok()?;
+        let pixel_size = self.pixel_size().ok()? as usize;
+        let offset = (y * self.info.width as usize + x) * pixel_size;
+        Some(pixel_to_rgba(&self.info.pixel_format, &self.pixels()[offset..offset + pixel_size]))
+    }
+
+    // Iterates over the pixels in row-major order, origin at the upper left corner, each
+    // one a slice of its stored bytes, e.g. B, G, R, A for ARGB32.
+    #[inline]
+    pub fn pixels_iter(&self) -> slice::ChunksExact<'_, u8> {
+        self.pixels().chunks_exact((self.bits_per_pixel() as usize).div_ceil(8))
+    }
+
+    // Gets the pixel at (`x`, `y`) in R, G, B, A order, whatever the pixel format.
+    // Alpha is 255 for formats without one or images declaring no attribute bits.
+    // Returns None if the coordinates are out of the image or the pixels were swapped
+    // by `to_rgba`.
+    pub fn get_pixel_rgba(&self, x: u16, y: u16) -> Option<[u8; 4]> {
+        let [r, g, b, a] = self.rgba_at(x as usize, y as usize)?;
+        Some([r, g, b, if self.info.has_alpha() { a } else { 255 }])
+    }
+
+    // Sets the pixel at (`x`, `y`) from R, G, B, A, converted to the pixel format as
+    // `convert` does. Returns false, leaving the image untouched, if the coordinates are
+    // out of the image, the image is indexed or its pixels were swapped by `to_rgba`.
+    pub fn set_pixel_rgba(&mut self, x: u16, y: u16, px: [u8; 4]) -> bool {
+        let (width, height) = self.dimensions();
+        if x >= width || y >= height || self.require_file_order().is_err() {
+            return false;
+        }
+        let Ok(pixel_size) = self.pixel_size() else {
+            return false;
+        };
+        let (format, pixel_size) = (self.info.pixel_format, pixel_size as usize);
+        let offset = (y as usize * width as usize + x as usize) * pixel_size;
+        rgba_to_pixel(&format, px, &mut self.pixels_mut()[offset..offset + pixel_size]);
+        true
+    }
+
+    // Iterates over the scanlines of the decoded data, top to bottom.
+    #[inline]
+    pub fn rows_iter(&self) -> slice::ChunksExact<'_, u8> {
+        self.pixels().chunks_exact(self.data.0.size() / self.info.height as usize)
+    }
+
+    // Converts every pixel to its luminance.
+    // Returns one byte per pixel in row-major order.
+    fn luma_plane(&self) -> Result<Vec<u8>, Error> {
+        self.require_file_order()?;
+        let pixel_size = self.pixel_size()? as usize;
+        Ok(self.pixels()
+            .chunks_exact(pixel_size)
+            .map(|px| {
+                let [r, g, b, _] = pixel_to_rgba(&self.info.pixel_format, px);
+                luma(r, g, b)
+            })
+            .collect())
+    }
+
+    #[inline]
+    fn get_pixel(&self, mut x: i32, mut y: i32) -> *mut u8 {
+        if x < 0 {
+            x = 0;
+        } else if x >= self.info.width as i32{
+            x = self.info.width as i32 - 1;
+        }
+
+        if y < 0 {
+            y = 0;
+        } else if y >= self.info.height as i32 {
+            y = self.info.height as i32 - 1;
         }
 
         let pixel_size = self.header.get_pixel_size().unwrap();
@@ -531,7 +2853,7 @@ impl Tga {
         }
     }
 
-    fn decode_data(&mut self, f: &mut File) -> Result<(), Error> {
+    fn decode_data<R: Read>(&mut self, f: &mut R, rle_mode: RleMode) -> Result<(), Error> {
         let mut pixels_count: usize = self.info.height as usize * self.info.width as usize;
         let pixel_size = self.header.get_pixel_size()?;
         let image_type = self.header.is_supported_image_type()?;
@@ -543,32 +2865,17 @@ impl Tga {
             TgaImageType::TrueColor | TgaImageType::GrayScale => {
                 unsafe {
                     // Convert pointer to slice.
-                    f.read(slice::from_raw_parts_mut(self.data.1, self.data.0.size()))?;
+                    f.read_exact(slice::from_raw_parts_mut(self.data.1, self.data.0.size())).map_err(data_read_error)?;
                 }
             },
             TgaImageType::ColorMapped => {
-                unsafe {
-                    let layout = Layout::from_size_align_unchecked(pixel_size as usize * mem::size_of::<u8>(), mem::size_of::<u8>());
-                    let ptr: *mut u8 = alloc::alloc(layout);
-                    let buf: &mut [u8] = slice::from_raw_parts_mut(ptr, pixel_size as usize);
-                    let mut index = 0;
-                    // current ptr's offset
-                    let mut offset: usize = 0;
-                    while pixels_count > 0 {
-                        if let Err(error)= f.read(buf) {
-                            alloc::dealloc(ptr, layout);
-                            return Err(error.into());
-                        }
-
-                        // Copy data from buf to tga.data.
-                        self.map.as_ref().unwrap().try_get_color(buf, index)?;
-                        ptr::copy_nonoverlapping(ptr, self.data.1.add(offset), pixel_size as usize);
-                        offset += pixel_size as usize;
-
-                        pixels_count -= 1;
-                        index += self.map.as_ref().unwrap().bytes_per_entry as u16;
-                    }
-                    alloc::dealloc(ptr, layout);
+                // Every pixel is a one byte index, looked up in the color map.
+                let map = self.map.as_ref().ok_or(Error::UnsupportedColorMapType)?;
+                let mut indices = vec![0u8; pixels_count];
+                f.read_exact(&mut indices).map_err(data_read_error)?;
+                let data = unsafe { slice::from_raw_parts_mut(self.data.1, self.data.0.size()) };
+                for (px, index) in data.chunks_exact_mut(pixel_size as usize).zip(indices) {
+                    map.try_get_color(px, index as u16)?;
                 }
             },
 
@@ -576,71 +2883,61 @@ impl Tga {
             TgaImageType::RLETrueColor | TgaImageType::RLEGrayScale | TgaImageType::RLEColorMapped => {
                 let mut is_run_length_packet = false;
                 let mut packet_count: u8 = 0;
-                let mut buf_size: u16 = 0;
                 // current ptr's offset
                 let mut offset: usize = 0;
 
-                if image_type == TgaImageType::RLEColorMapped {
-                    buf_size = self.map.as_ref().unwrap().bytes_per_entry as u16;
-                } else {
-                    buf_size = pixel_size as u16;
-                }
+                // Color mapped packets hold one byte indices, the buffer receives the
+                // color they are looked up to.
+                let buf_size = pixel_size as u16;
+                let read_size = if image_type == TgaImageType::RLEColorMapped { 1 } else { buf_size as usize };
 
-                let layout = unsafe { Layout::from_size_align_unchecked(buf_size as usize * mem::size_of::<u8>(), mem::size_of::<u8>()) };
-                let ptr: *mut u8 = unsafe { alloc::alloc(layout) };
+                // Released when dropped, on every return.
+                let packet = LayPtr::alloc(buf_size as usize, false)?;
+                let ptr = packet.1;
                 let buf: &mut [u8] = unsafe { slice::from_raw_parts_mut(ptr, buf_size as usize * mem::size_of::<u8>()) };
 
                 while pixels_count > 0 {
+                    // A packet still has pixels left at the start of a scanline.
+                    if rle_mode == RleMode::PerScanline && packet_count != 0 && pixels_count.is_multiple_of(self.info.width as usize) {
+                        return Err(Error::IllegalHeader);
+                    }
+
                     if packet_count == 0 {
                         let mut repetition_count_field: [u8; 1] = [255; 1];
-                        if let Err(error) = f.read(repetition_count_field.as_mut_slice()) {
-                            unsafe { alloc::dealloc(ptr, layout); }
-                            return Err(error.into());
-                        }
-                        if (repetition_count_field[0] & 0x80) == 0x80 {
-                            is_run_length_packet = true;
-                        } else {
-                            is_run_length_packet = false;
-                        }
+                        f.read_exact(repetition_count_field.as_mut_slice()).map_err(rle_read_error)?;
+                        is_run_length_packet = (repetition_count_field[0] & 0x80) == 0x80;
                         packet_count = (repetition_count_field[0] & 0x7F) + 1;
 
                         if is_run_length_packet {
-                            if let Err(error) = f.read(buf) {
-                                unsafe { alloc::dealloc(ptr, layout); }
-                                return Err(error.into());
-                            }
+                            f.read_exact(&mut buf[..read_size]).map_err(rle_read_error)?;
 
                             if image_type == TgaImageType::RLEColorMapped {
                                 let index = buf[0] as u16;
-                                if let Err(error) = self.map.as_ref().unwrap().try_get_color(buf, index) {
-                                    unsafe { alloc::dealloc(ptr, layout) }
-                                    return Err(error.into());
-                                }
+                                self.map.as_ref().unwrap().try_get_color(buf, index)?;
                             }
                         }
                     }
 
+                    // Never write past the pixel buffer, whatever the packets say.
+                    if offset + buf_size as usize > self.data.0.size() {
+                        return Err(Error::IllegalHeader);
+                    }
+
                     if is_run_length_packet {
                         unsafe {
                             ptr::copy_nonoverlapping(ptr, self.data.1.add(offset), buf_size as usize);
                         }
                     } else {
-                        if let Err(error) = f.read(buf) {
-                            unsafe { alloc::dealloc(ptr, layout); }
-                            return Err(error.into());
+                        f.read_exact(&mut buf[..read_size]).map_err(rle_read_error)?;
+
+                        if image_type == TgaImageType::RLEColorMapped {
+                            let index = buf[0] as u16;
+                            self.map.as_ref().unwrap().try_get_color(buf, index)?;
                         }
 
                         unsafe {
                             ptr::copy_nonoverlapping(ptr, self.data.1.add(offset), buf_size as usize);
                         }
-
-                        if image_type == TgaImageType::RLEColorMapped {
-                            let index = buf[0] as u16;
-                            if let Err(error) = self.map.as_ref().unwrap().try_get_color(buf, index) {
-                                unsafe { alloc::dealloc(ptr, layout) }
-                                return Err(error.into());
-                            }
-                        }
                     }
 
                     pixels_count -= 1;
@@ -648,7 +2945,10 @@ impl Tga {
                     offset += pixel_size as usize;
                 }
 
-                unsafe { alloc::dealloc(ptr, layout); }
+                // The last packet holds more pixels than the image.
+                if packet_count != 0 {
+                    return Err(Error::IllegalHeader);
+                }
             },
         }
 
@@ -656,11 +2956,125 @@ impl Tga {
     }
 }
 
+impl<'a> TgaView<'a> {
+    // Parses the header of an image held in memory, e.g. embedded with `include_bytes!`,
+    // and borrows its pixels.
+    // Returns Err(UnsupportedImageType) for run-length encoded or color mapped files and
+    // files stored right to left, which `Tga::from_bytes` decodes, and
+    // Err(UnexpectedEof) if `bytes` ends before the pixels do.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, Error> {
+        let header = TgaHeader::from_file(&mut Cursor::new(bytes))?;
+        let info = TgaInfo::from_tga_header(&header)?;
+        match header.is_supported_image_type()? {
+            TgaImageType::TrueColor | TgaImageType::GrayScale => {},
+            _ => return Err(Error::UnsupportedImageType),
+        }
+        if header.image_descripter & 0x10 != 0 {
+            return Err(Error::UnsupportedImageType);
+        }
+
+        let id_start = HEADER_SIZE;
+        let id_end = id_start + header.id_length as usize;
+        let map_size = header.map_length as usize * bits_to_bytes(header.map_entry_size.into());
+        let data_start = id_end + map_size;
+        let data_end = data_start + image_size(info.width, info.height, header.get_pixel_size()?)?;
+        if bytes.len() < data_end {
+            return Err(Error::UnexpectedEof);
+        }
+
+        Ok(Self { header, info, image_id: &bytes[id_start..id_end], pixels: &bytes[data_start..data_end] })
+    }
+
+    // Gets the dimensions of the image as (width, height).
+    #[inline]
+    pub fn dimensions(&self) -> (u16, u16) {
+        (self.info.width, self.info.height)
+    }
+
+    // Gets the image ID field.
+    #[inline]
+    pub fn image_id(&self) -> &'a [u8] {
+        self.image_id
+    }
+
+    // Gets the pixels as stored in the file: tightly packed scanlines in the byte order of
+    // the pixel format, bottom to top unless `is_top_down`. `rows_iter` hides the order.
+    #[inline]
+    pub fn pixels(&self) -> &'a [u8] {
+        self.pixels
+    }
+
+    // Returns true if the first stored scanline is the top one.
+    #[inline]
+    pub fn is_top_down(&self) -> bool {
+        self.header.image_descripter & 0x20 != 0
+    }
+
+    // Iterates over the scanlines top to bottom, as `Tga::rows_iter` does after decoding.
+    pub fn rows_iter(&self) -> impl DoubleEndedIterator<Item = &'a [u8]> + ExactSizeIterator {
+        let pixels = self.pixels;
+        let height = self.info.height as usize;
+        let row_size = pixels.len() / height;
+        let top_down = self.is_top_down();
+        (0..height).map(move |y| {
+            let row = if top_down { y } else { height - 1 - y };
+            &pixels[row * row_size..(row + 1) * row_size]
+        })
+    }
+
+    // Copies the view into an owned image, origin at the upper left corner.
+    pub fn to_tga(&self) -> Result<Tga, Error> {
+        let pixels: Vec<u8> = self.rows_iter().flatten().copied().collect();
+        let mut tga = Tga::from_raw(self.info.width, self.info.height, self.info.pixel_format, &pixels)?;
+        tga.info.attribute_bits = self.info.attribute_bits;
+        tga.set_image_id(self.image_id)?;
+        Ok(tga)
+    }
+}
+
+impl<'a> TgaRef<'a> {
+    // Views the image if `TgaView` supports it, otherwise decodes it with `Tga::from_bytes`.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, Error> {
+        match TgaView::new(bytes) {
+            Ok(view) => Ok(TgaRef::View(view)),
+            Err(Error::UnsupportedImageType) => Ok(TgaRef::Owned(Box::new(Tga::from_bytes(bytes)?))),
+            Err(error) => Err(error),
+        }
+    }
+
+    pub fn info(&self) -> &TgaInfo {
+        match self {
+            TgaRef::View(view) => &view.info,
+            TgaRef::Owned(tga) => &tga.info,
+        }
+    }
+
+    // Iterates over the scanlines top to bottom, whichever the variant.
+    pub fn rows_iter(&self) -> Box<dyn Iterator<Item = &[u8]> + '_> {
+        match self {
+            TgaRef::View(view) => Box::new(view.rows_iter()),
+            TgaRef::Owned(tga) => Box::new(tga.rows_iter()),
+        }
+    }
+}
+
 // Checks if the picture size is corrent.
 // Returns false if invalid dimensions, otherwise returns true.
 #[inline]
 fn check_dimensions(width: u32, height: u32) -> bool {
-    width <= 0 || width > TGA_MAX_IMAGE_DIMENSIONS || height <= 0 || height > TGA_MAX_IMAGE_DIMENSIONS
+    (1..=TGA_MAX_IMAGE_DIMENSIONS).contains(&width) && (1..=TGA_MAX_IMAGE_DIMENSIONS).contains(&height)
+}
+
+// Computes the bytes needed by the pixels of an image.
+// Returns Err(ErrorOutOfMemory) if the size doesn't fit in the address space,
+// which may happen for large images on 32 bits targets.
+#[inline]
+fn image_size(width: u16, height: u16, pixel_size: u32) -> Result<usize, Error> {
+    (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|count| count.checked_mul(pixel_size as usize))
+        .filter(|size| *size <= isize::MAX as usize)
+        .ok_or(Error::ErrorOutOfMemory)
 }
 
 // Convert bits to integer bytes. E.g. 8 bits to 1 byte, 9 bits to 2 bytes.
@@ -671,3 +3085,374 @@ pub fn bits_to_bytes(bits_count: usize) -> usize {
     }
     (bits_count - 1) / 8 + 1
 }
+
+// Maps a texel coordinate into [0, size) according to the wrap mode.
+#[inline]
+fn wrap_coordinate(i: i64, size: i64, wrap: WrapMode) -> usize {
+    let i = match wrap {
+        WrapMode::Clamp => i.clamp(0, size - 1),
+        WrapMode::Repeat => i.rem_euclid(size),
+        WrapMode::Mirror => {
+            let m = i.rem_euclid(2 * size);
+            if m < size { m } else { 2 * size - 1 - m }
+        },
+    };
+    i as usize
+}
+
+// Box blurs `len` samples of `data` starting at `start` and spaced by `step` bytes.
+// The window slides with a running sum, samples outside the line are clamped to its ends.
+fn box_blur_line(data: &mut [u8], start: usize, step: usize, len: usize, radius: usize) {
+    let line: Vec<u32> = (0..len).map(|i| data[start + i * step] as u32).collect();
+    let at = |i: isize| line[i.clamp(0, len as isize - 1) as usize];
+    let r = radius as isize;
+    let window = 2 * radius as u32 + 1;
+    let mut sum: u32 = (-r..=r).map(at).sum();
+
+    for i in 0..len {
+        data[start + i * step] = ((sum + window / 2) / window) as u8;
+        sum += at(i as isize + r + 1);
+        sum -= at(i as isize - r);
+    }
+}
+
+// Computes the euclidean distance from every pixel to the nearest seed pixel with
+// two raster passes propagating the offset to the nearest seed (8SSEDT).
+// Pixels are at infinite distance if there is no seed at all.
+fn distance_transform(seeds: &[bool], width: usize, height: usize) -> Vec<f32> {
+    const FAR: i64 = 1 << 20;
+    let mut offsets: Vec<(i64, i64)> = seeds.iter().map(|s| if *s { (0, 0) } else { (FAR, FAR) }).collect();
+
+    // Takes the neighbor's nearest seed if it's closer than the current one.
+    let relax = |offsets: &mut [(i64, i64)], x: usize, y: usize, dx: i64, dy: i64| {
+        let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+        if nx < 0 || ny < 0 || nx >= width as i64 || ny >= height as i64 {
+            return;
+        }
+        let (ox, oy) = offsets[ny as usize * width + nx as usize];
+        let candidate = (ox + dx, oy + dy);
+        let current = offsets[y * width + x];
+        if candidate.0 * candidate.0 + candidate.1 * candidate.1 < current.0 * current.0 + current.1 * current.1 {
+            offsets[y * width + x] = candidate;
+        }
+    };
+
+    for y in 0..height {
+        for x in 0..width {
+            for (dx, dy) in [(-1, 0), (0, -1), (-1, -1), (1, -1)] {
+                relax(&mut offsets, x, y, dx, dy);
+            }
+        }
+        for x in (0..width).rev() {
+            relax(&mut offsets, x, y, 1, 0);
+        }
+    }
+    for y in (0..height).rev() {
+        for x in (0..width).rev() {
+            for (dx, dy) in [(1, 0), (0, 1), (-1, 1), (1, 1)] {
+                relax(&mut offsets, x, y, dx, dy);
+            }
+        }
+        for x in 0..width {
+            relax(&mut offsets, x, y, -1, 0);
+        }
+    }
+
+    offsets
+        .iter()
+        .map(|(ox, oy)| match ox.abs() >= FAR / 2 {
+            true => f32::INFINITY,
+            false => ((ox * ox + oy * oy) as f32).sqrt(),
+        })
+        .collect()
+}
+
+// Lookup table of the CRC-32 (IEEE 802.3) polynomial.
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xedb88320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+};
+
+// Computes the CRC-32 (IEEE 802.3) of `data`.
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |c, b| CRC32_TABLE[((c ^ *b as u32) & 0xff) as usize] ^ (c >> 8))
+}
+
+// Checks whether the data ends with the TGA 2.0 footer signature, without decoding anything.
+// The position of the reader is restored afterward.
+pub fn is_tga_v2<R: Read + Seek>(reader: &mut R) -> Result<bool, Error> {
+    let position = reader.stream_position()?;
+    let len = reader.seek(SeekFrom::End(0))?;
+    let mut footer = [0u8; FOOTER_SIZE];
+    let result = match len >= (HEADER_SIZE + FOOTER_SIZE) as u64 {
+        true => reader
+            .seek(SeekFrom::End(-(FOOTER_SIZE as i64)))
+            .and_then(|_| reader.read_exact(&mut footer))
+            .map(|_| &footer[8..] == FOOTER_SIGNATURE),
+        false => Ok(false),
+    };
+    reader.seek(SeekFrom::Start(position))?;
+
+    Ok(result?)
+}
+
+// Guesses whether the data starts with a TGA header. TGA has no magic number, so this is
+// only a heuristic: the image type, color map type, dimensions, pixel depth and reserved
+// descriptor bits must be plausible. The position of the reader is restored afterward.
+pub fn looks_like_tga<R: Read + Seek>(reader: &mut R) -> bool {
+    let Ok(position) = reader.stream_position() else {
+        return false;
+    };
+    let mut bytes = [0u8; HEADER_SIZE];
+    let read = reader.read_exact(&mut bytes);
+    if reader.seek(SeekFrom::Start(position)).is_err() || read.is_err() {
+        return false;
+    }
+
+    let header = TgaHeader::from_bytes(&bytes);
+    let color_mapped = matches!(header.is_supported_image_type(), Ok(TgaImageType::ColorMapped | TgaImageType::RLEColorMapped));
+    header.map_type <= 1
+        && (!color_mapped || (header.map_type == 1 && header.map_length > 0))
+        && header.image_width > 0
+        && header.image_height > 0
+        && header.image_descripter & 0xc0 == 0
+        && header.get_pixel_format().is_ok()
+}
+
+// Counts the bits that differ between two hashes, e.g. from `Tga::dhash`.
+// Near-identical images have a small distance.
+#[inline]
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+// Builds the TGA 2.0 footer: the extension and developer area offsets (0 when absent)
+// followed by the signature.
+fn encode_footer(extension_offset: u32, developer_offset: u32) -> [u8; FOOTER_SIZE] {
+    let mut footer = [0u8; FOOTER_SIZE];
+    footer[..4].copy_from_slice(&extension_offset.to_le_bytes());
+    footer[4..8].copy_from_slice(&developer_offset.to_le_bytes());
+    footer[8..].copy_from_slice(FOOTER_SIGNATURE);
+    footer
+}
+
+// Reads the extension and developer area offsets of the footer of the image starting
+// at `start`. Returns None if the file doesn't end with a TGA 2.0 footer.
+fn read_footer<R: Read + Seek>(reader: &mut R, start: u64) -> Result<Option<(u32, u32)>, Error> {
+    let end = reader.seek(SeekFrom::End(0))?;
+    if end < start + (HEADER_SIZE + FOOTER_SIZE) as u64 {
+        return Ok(None);
+    }
+    let mut footer = [0u8; FOOTER_SIZE];
+    reader.seek(SeekFrom::End(-(FOOTER_SIZE as i64)))?;
+    reader.read_exact(&mut footer)?;
+    if &footer[8..] != FOOTER_SIGNATURE {
+        return Ok(None);
+    }
+    let extension_offset = u32::from_le_bytes([footer[0], footer[1], footer[2], footer[3]]);
+    let developer_offset = u32::from_le_bytes([footer[4], footer[5], footer[6], footer[7]]);
+    Ok(Some((extension_offset, developer_offset)))
+}
+
+// Parses the extension area of the image starting at `start`.
+fn read_extension_at<R: Read + Seek>(reader: &mut R, start: u64) -> Result<Option<TgaExtension>, Error> {
+    let extension_offset = match read_footer(reader, start)? {
+        Some((offset, _)) if offset != 0 => offset,
+        _ => return Ok(None),
+    };
+
+    let mut area = [0u8; EXTENSION_SIZE];
+    reader.seek(SeekFrom::Start(start + extension_offset as u64))?;
+    reader.read_exact(&mut area).map_err(|_| Error::IllegalHeader)?;
+    if (u16::from_le_bytes([area[0], area[1]]) as usize) < EXTENSION_SIZE {
+        return Err(Error::IllegalHeader);
+    }
+
+    let word = |i: usize| u16::from_le_bytes([area[i], area[i + 1]]);
+    let dword = |i: usize| u32::from_le_bytes([area[i], area[i + 1], area[i + 2], area[i + 3]]);
+    let text = |i: usize, len: usize| {
+        let field = &area[i..i + len];
+        let end = field.iter().position(|c| *c == 0).unwrap_or(len);
+        String::from_utf8_lossy(&field[..end]).into_owned()
+    };
+
+    Ok(Some(TgaExtension {
+        author_name: text(2, 41),
+        author_comments: [text(43, 81), text(124, 81), text(205, 81), text(286, 81)],
+        timestamp: [word(367), word(369), word(371), word(373), word(375), word(377)],
+        job_name: text(379, 41),
+        job_time: [word(420), word(422), word(424)],
+        software_id: text(426, 41),
+        software_version: (word(467), area[469]),
+        key_color: dword(470),
+        pixel_aspect_ratio: (word(474), word(476)),
+        gamma: (word(478), word(480)),
+        color_correction_offset: dword(482),
+        postage_stamp_offset: dword(486),
+        scan_line_offset: dword(490),
+        attributes_type: area[494],
+    }))
+}
+
+// Reads the data of the developer area entry tagged `tag`, offsets being relative to
+// `start`. Returns None if the file has no footer, no developer area or no such tag.
+fn read_developer_entry<R: Read + Seek>(reader: &mut R, start: u64, tag: u16) -> Result<Option<Vec<u8>>, Error> {
+    Ok(read_developer_fields(reader, start)?
+        .into_iter()
+        .find(|field| field.tag == tag)
+        .map(|field| field.data))
+}
+
+// Parses the developer directory of the image starting at `start` and reads every field.
+// Returns Err(IllegalHeader) if the directory or a field lies beyond the end of the file.
+fn read_developer_fields<R: Read + Seek>(reader: &mut R, start: u64) -> Result<Vec<DeveloperField>, Error> {
+    let directory_offset = match read_footer(reader, start)? {
+        Some((_, offset)) if offset != 0 => offset,
+        _ => return Ok(Vec::new()),
+    };
+    let end = reader.seek(SeekFrom::End(0))?;
+
+    reader.seek(SeekFrom::Start(start + directory_offset as u64))?;
+    let mut count = [0u8; 2];
+    reader.read_exact(&mut count).map_err(|_| Error::IllegalHeader)?;
+    let mut entries = vec![0u8; u16::from_le_bytes(count) as usize * 10];
+    reader.read_exact(&mut entries).map_err(|_| Error::IllegalHeader)?;
+
+    let mut fields = Vec::with_capacity(entries.len() / 10);
+    for entry in entries.chunks_exact(10) {
+        let offset = u32::from_le_bytes([entry[2], entry[3], entry[4], entry[5]]);
+        let size = u32::from_le_bytes([entry[6], entry[7], entry[8], entry[9]]);
+        // Checked before allocating, the size comes from the file.
+        if start + offset as u64 + size as u64 > end {
+            return Err(Error::IllegalHeader);
+        }
+        let mut data = vec![0u8; size as usize];
+        reader.seek(SeekFrom::Start(start + offset as u64))?;
+        reader.read_exact(&mut data)?;
+        fields.push(DeveloperField { tag: u16::from_le_bytes([entry[0], entry[1]]), offset, size, data });
+    }
+    Ok(fields)
+}
+
+// Maps a read failure inside uncompressed pixel data to an error.
+#[inline]
+fn data_read_error(error: std::io::Error) -> Error {
+    match error.kind() {
+        std::io::ErrorKind::UnexpectedEof => Error::UnexpectedEof,
+        _ => error.into(),
+    }
+}
+
+// Maps a read failure inside run-length encoded data to an error.
+// Running out of data means the packets don't cover the image, so the file is malformed.
+#[inline]
+fn rle_read_error(error: std::io::Error) -> Error {
+    match error.kind() {
+        std::io::ErrorKind::UnexpectedEof => Error::IllegalHeader,
+        _ => error.into(),
+    }
+}
+
+// Appends the run-length packets encoding `pixels` to `out`.
+// Repeated pixels become run-length packets, others are grouped in raw packets,
+// each packet holding at most 128 pixels.
+fn encode_rle_packets(pixels: &[u8], pixel_size: usize, out: &mut Vec<u8>) {
+    let count = pixels.len() / pixel_size;
+    let px = |i: usize| &pixels[i * pixel_size..(i + 1) * pixel_size];
+    let mut i = 0;
+
+    while i < count {
+        let mut run = 1;
+        while i + run < count && run < 128 && px(i + run) == px(i) {
+            run += 1;
+        }
+
+        if run > 1 {
+            out.push(0x80 | (run - 1) as u8);
+            out.extend_from_slice(px(i));
+            i += run;
+        } else {
+            let start = i;
+            while i < count && i - start < 128 && !(i + 1 < count && px(i) == px(i + 1)) {
+                i += 1;
+            }
+            out.push((i - start - 1) as u8);
+            out.extend_from_slice(&pixels[start * pixel_size..i * pixel_size]);
+        }
+    }
+}
+
+// Unpacks one pixel stored in the given format into R, G, B, A order.
+// True color pixels are stored as BGR(A), RGB555 as a little-endian A1R5G5B5 word
+// and BW16 as a little-endian 16-bit sample.
+#[inline]
+pub(crate) fn pixel_to_rgba(format: &TgaPixelFormat, px: &[u8]) -> [u8; 4] {
+    match format {
+        TgaPixelFormat::BW8 => [px[0], px[0], px[0], 255],
+        TgaPixelFormat::BW16 => [px[1], px[1], px[1], 255],
+        TgaPixelFormat::RGB555 => {
+            let word = u16::from_le_bytes([px[0], px[1]]);
+            [expand_5bits(word >> 10), expand_5bits(word >> 5), expand_5bits(word), 255]
+        },
+        TgaPixelFormat::RGB24 => [px[2], px[1], px[0], 255],
+        TgaPixelFormat::ARGB32 => [px[2], px[1], px[0], px[3]],
+    }
+}
+
+// Packs R, G, B, A into the start of `px` in the stored layout of the given format,
+// the reverse of `pixel_to_rgba`. Grayscale formats get the luma of the color.
+// Returns the pixel size. `px` must hold at least that many bytes.
+#[inline]
+pub(crate) fn rgba_to_pixel(format: &TgaPixelFormat, [r, g, b, a]: [u8; 4], px: &mut [u8]) -> usize {
+    match format {
+        TgaPixelFormat::BW8 => {
+            px[0] = luma(r, g, b);
+            1
+        },
+        TgaPixelFormat::BW16 => {
+            px[..2].copy_from_slice(&(luma(r, g, b) as u16 * 257).to_le_bytes());
+            2
+        },
+        TgaPixelFormat::RGB555 => {
+            let word = ((a >= 128) as u16) << 15
+                | (r as u16 >> 3) << 10
+                | (g as u16 >> 3) << 5
+                | b as u16 >> 3;
+            px[..2].copy_from_slice(&word.to_le_bytes());
+            2
+        },
+        // Stored as BGR(A).
+        TgaPixelFormat::RGB24 => {
+            px[..3].copy_from_slice(&[b, g, r]);
+            3
+        },
+        TgaPixelFormat::ARGB32 => {
+            px[..4].copy_from_slice(&[b, g, r, a]);
+            4
+        },
+    }
+}
+
+// Scales the low 5 bits of a channel to the full 8 bits range.
+#[inline]
+fn expand_5bits(channel: u16) -> u8 {
+    let c = (channel & 0x1f) as u8;
+    (c << 3) | (c >> 2)
+}
+
+// Perceptual luminance with the Rec. 601 weights (0.299, 0.587, 0.114).
+#[inline]
+fn luma(r: u8, g: u8, b: u8) -> u8 {
+    ((299 * r as u32 + 587 * g as u32 + 114 * b as u32 + 500) / 1000) as u8
+}